@@ -14,50 +14,105 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{io, collections::{BTreeMap, HashMap}};
+mod persist;
+
+use std::{ops::Bound, path::{Path, PathBuf}, sync::Arc, collections::{BTreeMap, HashMap}};
 use parking_lot::RwLock;
-use kvdb::{DBValue, DBTransaction, KeyValueDB, DBOp};
+use kvdb::{DBValue, DBTransaction, KeyValueDB, KeyValueDBSnapshot, DBOp, Direction, ColumnStats, MergeFn, Error, Result};
+use kvdb::migration::ColumnManager;
+
+// Columns are reference counted so that snapshots can share them with the database.
+// A column is only copied when it is written to while a snapshot still holds it.
+type Columns = HashMap<Option<u32>, Arc<BTreeMap<Vec<u8>, DBValue>>>;
 
 /// A key-value database fulfilling the `KeyValueDB` trait, living in memory.
 /// This is generally intended for tests and is not particularly optimized.
+///
+/// Databases created with `open` are persisted to a file on `flush` and on drop.
 #[derive(Default)]
 pub struct InMemory {
-	columns: RwLock<HashMap<Option<u32>, BTreeMap<Vec<u8>, DBValue>>>,
+	columns: RwLock<Columns>,
+	merge_operators: HashMap<Option<u32>, MergeFn>,
+	// File the columns are written to on `flush`, if persistent.
+	path: Option<PathBuf>,
+}
+
+/// A read-only view of an `InMemory` database at the time `snapshot` was called.
+pub struct InMemorySnapshot {
+	columns: Columns,
 }
 
 /// Create an in-memory database with the given number of columns.
 /// Columns will be indexable by 0..`num_cols`
 pub fn create(num_cols: u32) -> InMemory {
 	let mut cols = HashMap::new();
-	cols.insert(None, BTreeMap::new());
+	cols.insert(None, Arc::new(BTreeMap::new()));
 
 	for idx in 0..num_cols {
-		cols.insert(Some(idx), BTreeMap::new());
+		cols.insert(Some(idx), Arc::new(BTreeMap::new()));
 	}
 
 	InMemory {
-		columns: RwLock::new(cols)
+		columns: RwLock::new(cols),
+		merge_operators: HashMap::new(),
+		path: None,
 	}
 }
 
-impl KeyValueDB for InMemory {
-	fn get(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<DBValue>> {
-		let columns = self.columns.read();
-		match columns.get(&col) {
-			None => Err(io::Error::new(io::ErrorKind::Other, format!("No such column family: {:?}", col))),
-			Some(map) => Ok(map.get(key).cloned()),
+/// Open a persistent in-memory database with the given number of columns, loading
+/// its contents from the file at `path` if it exists.
+///
+/// The whole database is written to the file on `flush`. Fails with `Error::Corruption`
+/// if the file is not a valid database file.
+pub fn open<P: AsRef<Path>>(path: P, num_cols: u32) -> Result<InMemory> {
+	let path = path.as_ref();
+	let mut db = create(num_cols);
+	if path.exists() {
+		let mut columns = persist::load(path)?;
+		for (col, map) in db.columns.get_mut().drain() {
+			columns.entry(col).or_insert(map);
 		}
+		*db.columns.get_mut() = columns;
+	}
+	db.path = Some(path.to_owned());
+	Ok(db)
+}
+
+impl InMemory {
+	/// Register the merge function of a column.
+	/// Merges into columns without one fail on `write` and are discarded by `write_buffered`.
+	pub fn with_merge_operator(mut self, col: Option<u32>, merge: MergeFn) -> Self {
+		self.merge_operators.insert(col, merge);
+		self
+	}
+}
+
+fn column(columns: &Columns, col: Option<u32>) -> Result<&Arc<BTreeMap<Vec<u8>, DBValue>>> {
+	columns.get(&col).ok_or(Error::UnknownColumn(col))
+}
+
+fn get(columns: &Columns, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>> {
+	Ok(column(columns, col)?.get(key).cloned())
+}
+
+fn get_by_prefix(columns: &Columns, col: Option<u32>, prefix: &[u8]) -> Result<Option<Box<[u8]>>> {
+	Ok(column(columns, col)?.iter()
+		.find(|&(ref k ,_)| k.starts_with(prefix))
+		.map(|(_, v)| v.to_vec().into_boxed_slice()))
+}
+
+impl KeyValueDB for InMemory {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>> {
+		get(&self.columns.read(), col, key)
 	}
 
-	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>> {
+	fn get_many(&self, col: Option<u32>, keys: &[&[u8]]) -> Result<Vec<Option<DBValue>>> {
 		let columns = self.columns.read();
-		match columns.get(&col) {
-			None => None,
-			Some(map) =>
-				map.iter()
-					.find(|&(ref k ,_)| k.starts_with(prefix))
-					.map(|(_, v)| v.to_vec().into_boxed_slice())
-		}
+		keys.iter().map(|key| get(&columns, col, key)).collect()
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Result<Option<Box<[u8]>>> {
+		get_by_prefix(&self.columns.read(), col, prefix)
 	}
 
 	fn write_buffered(&self, transaction: DBTransaction) {
@@ -67,48 +122,553 @@ impl KeyValueDB for InMemory {
 			match op {
 				DBOp::Insert { col, key, value } => {
 					if let Some(col) = columns.get_mut(&col) {
-						col.insert(key.into_vec(), value);
+						Arc::make_mut(col).insert(key.into_vec(), value);
 					}
 				},
 				DBOp::Delete { col, key } => {
 					if let Some(col) = columns.get_mut(&col) {
-						col.remove(&*key);
+						Arc::make_mut(col).remove(&*key);
+					}
+				},
+				DBOp::DeletePrefix { col, prefix } => {
+					if let Some(col) = columns.get_mut(&col) {
+						let col = Arc::make_mut(col);
+						let mut deleted = col.split_off(&prefix[..]);
+						if let Some(end) = kvdb::end_prefix(&prefix) {
+							col.append(&mut deleted.split_off(&end[..]));
+						}
+					}
+				},
+				DBOp::Merge { col, key, operand } => {
+					if let (Some(column), Some(merge)) = (columns.get_mut(&col), self.merge_operators.get(&col)) {
+						let column = Arc::make_mut(column);
+						let value = merge(&key, column.get(&*key).map(|v| &v[..]), &[&operand]);
+						column.insert(key.into_vec(), DBValue::from_vec(value));
 					}
 				},
 			}
 		}
 	}
 
-	fn flush(&self) -> io::Result<()> {
+	fn write(&self, transaction: DBTransaction) -> Result<()> {
+		// checked before applying any change, so that the transaction is written entirely or not at all.
+		let unmerged = transaction.ops.iter().any(|op| match *op {
+			DBOp::Merge { col, .. } => !self.merge_operators.contains_key(&col),
+			_ => false,
+		});
+		if unmerged {
+			return Err(Error::Unsupported("No merge operator registered for the column"));
+		}
+		self.write_buffered(transaction);
+		self.flush()
+	}
+
+	fn flush(&self) -> Result<()> {
+		match self.path {
+			// the lock is held while writing, so that concurrent flushes don't interleave.
+			Some(ref path) => persist::save(&self.columns.read(), path),
+			None => Ok(()),
+		}
+	}
+
+	fn iter<'a>(&'a self, col: Option<u32>) -> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>> {
+		let map = (**column(&self.columns.read(), col)?).clone(); // TODO: worth optimizing at all?
+		Ok(Box::new(
+			map.into_iter()
+				.map(|(k, v)| (k.into_boxed_slice(), v.into_vec().into_boxed_slice()))
+		))
+	}
+
+	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>>
+	{
+		let map = (**column(&self.columns.read(), col)?).clone();
+		Ok(Box::new(
+			map.into_iter()
+				.skip_while(move |&(ref k, _)| !k.starts_with(prefix))
+				.map(|(k, v)| (k.into_boxed_slice(), v.into_vec().into_boxed_slice()))
+		))
+	}
+
+	fn iter_range<'a>(
+		&'a self,
+		col: Option<u32>,
+		start: Bound<&'a [u8]>,
+		end: Bound<&'a [u8]>,
+		direction: Direction,
+	) -> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>> {
+		// `BTreeMap::range` panics on ranges with the start after the end.
+		let is_empty = match (start, end) {
+			(Bound::Included(s), Bound::Included(e)) => s > e,
+			(Bound::Included(s), Bound::Excluded(e))
+				| (Bound::Excluded(s), Bound::Included(e))
+				| (Bound::Excluded(s), Bound::Excluded(e)) => s >= e,
+			_ => false,
+		};
+		let columns = self.columns.read();
+		let map = column(&columns, col)?;
+		if is_empty {
+			return Ok(Box::new(None.into_iter()));
+		}
+		let range = map.range::<[u8], _>((start, end))
+			.map(|(k, v)| (k.clone().into_boxed_slice(), v.to_vec().into_boxed_slice()));
+		let data: Vec<_> = match direction {
+			Direction::Forward => range.collect(),
+			Direction::Reverse => range.rev().collect(),
+		};
+		Ok(Box::new(data.into_iter()))
+	}
+
+	// All the data is kept in memory, so there are no SST files.
+	fn column_stats(&self, col: Option<u32>) -> Result<ColumnStats> {
+		let columns = self.columns.read();
+		let map = column(&columns, col)?;
+		let size = map.iter().map(|(k, v)| (k.len() + v.len()) as u64).sum();
+		Ok(ColumnStats {
+			num_keys: map.len() as u64,
+			live_data_size: size,
+			num_sst_files: 0,
+			memtable_size: size,
+		})
+	}
+
+	fn snapshot<'a>(&'a self) -> Result<Box<dyn KeyValueDBSnapshot + 'a>> {
+		Ok(Box::new(InMemorySnapshot {
+			columns: self.columns.read().clone(),
+		}))
+	}
+
+	// Replaces the database with the one persisted at `new_db`, which is moved to the path
+	// of this database if it is persistent.
+	fn restore(&self, new_db: &str) -> Result<()> {
+		let restored = persist::load(Path::new(new_db))?;
+		// the lock is held while moving the file, so that a flush doesn't overwrite it, and the file
+		// is moved before the columns are replaced, so that a failure leaves the database unchanged.
+		let mut columns = self.columns.write();
+		if let Some(ref path) = self.path {
+			std::fs::rename(new_db, path)?;
+		}
+		*columns = restored;
+		Ok(())
+	}
+}
+
+impl Drop for InMemory {
+	fn drop(&mut self) {
+		// persist all the changes if we can.
+		let _ = self.flush();
+	}
+}
+
+impl ColumnManager for InMemory {
+	fn num_columns(&self) -> u32 {
+		self.columns.read().len() as u32 - 1
+	}
+
+	fn add_column(&self) -> Result<()> {
+		let mut columns = self.columns.write();
+		let col = columns.len() as u32 - 1;
+		columns.insert(Some(col), Arc::new(BTreeMap::new()));
 		Ok(())
 	}
 
-	fn iter<'a>(&'a self, col: Option<u32>) -> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a> {
-		match self.columns.read().get(&col) {
-			Some(map) => Box::new( // TODO: worth optimizing at all?
-				map.clone()
-					.into_iter()
-					.map(|(k, v)| (k.into_boxed_slice(), v.into_vec().into_boxed_slice()))
-			),
-			None => Box::new(None.into_iter()),
+	fn drop_column(&self) -> Result<()> {
+		let mut columns = self.columns.write();
+		let cols = columns.len() as u32 - 1;
+		if cols > 0 {
+			columns.remove(&Some(cols - 1));
 		}
+		Ok(())
+	}
+}
+
+impl KeyValueDBSnapshot for InMemorySnapshot {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>> {
+		get(&self.columns, col, key)
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Result<Option<Box<[u8]>>> {
+		get_by_prefix(&self.columns, col, prefix)
+	}
+
+	fn iter<'a>(&'a self, col: Option<u32>) -> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>> {
+		Ok(Box::new(
+			column(&self.columns, col)?.iter()
+				.map(|(k, v)| (k.clone().into_boxed_slice(), v.to_vec().into_boxed_slice()))
+		))
 	}
 
 	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
-		-> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>>
 	{
-		match self.columns.read().get(&col) {
-			Some(map) => Box::new(
-				map.clone()
-					.into_iter()
-					.skip_while(move |&(ref k, _)| !k.starts_with(prefix))
-					.map(|(k, v)| (k.into_boxed_slice(), v.into_vec().into_boxed_slice()))
-			),
-			None => Box::new(None.into_iter()),
+		Ok(Box::new(
+			column(&self.columns, col)?.iter()
+				.skip_while(move |&(ref k, _)| !k.starts_with(prefix))
+				.map(|(k, v)| (k.clone().into_boxed_slice(), v.to_vec().into_boxed_slice()))
+		))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn snapshot_is_not_affected_by_writes() {
+		let db = create(1);
+
+		let mut batch = db.transaction();
+		batch.put(None, b"foo", b"bar");
+		batch.put(Some(0), b"baz", b"qux");
+		db.write(batch).unwrap();
+
+		let snapshot = db.snapshot().unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(None, b"foo", b"changed");
+		batch.delete(Some(0), b"baz");
+		batch.put(Some(0), b"new", b"value");
+		db.write(batch).unwrap();
+
+		assert_eq!(db.get(None, b"foo").unwrap().unwrap().as_ref(), b"changed");
+		assert_eq!(snapshot.get(None, b"foo").unwrap().unwrap().as_ref(), b"bar");
+		assert_eq!(snapshot.get(Some(0), b"baz").unwrap().unwrap().as_ref(), b"qux");
+		assert!(snapshot.get(Some(0), b"new").unwrap().is_none());
+
+		let contents: Vec<_> = snapshot.iter(Some(0)).unwrap().collect();
+		assert_eq!(contents.len(), 1);
+		assert_eq!(&*contents[0].0, b"baz");
+		assert_eq!(&*snapshot.get_by_prefix(Some(0), b"ba").unwrap().unwrap(), b"qux");
+		match snapshot.get(Some(1), b"foo") {
+			Err(Error::UnknownColumn(Some(1))) => {},
+			other => panic!("unexpected result: {:?}", other),
 		}
 	}
 
-	fn restore(&self, _new_db: &str) -> io::Result<()> {
-		Err(io::Error::new(io::ErrorKind::Other, "Attempted to restore in-memory database"))
+	#[test]
+	fn delete_prefix() {
+		let db = create(0);
+
+		let mut batch = db.transaction();
+		for key in &[&[0][..], &[1, 0], &[1, 255], &[1, 255, 255], &[2]] {
+			batch.put(None, key, key);
+		}
+		db.write(batch).unwrap();
+
+		let mut batch = db.transaction();
+		batch.delete_prefix(None, &[1]);
+		batch.put(None, &[1, 1], &[1, 1]);
+		db.write(batch).unwrap();
+
+		let keys = db.iter(None).unwrap().map(|(k, _)| k.into_vec()).collect::<Vec<_>>();
+		assert_eq!(keys, vec![vec![0], vec![1, 1], vec![2]]);
+
+		let mut batch = db.transaction();
+		batch.delete_prefix(None, &[]);
+		db.write(batch).unwrap();
+		assert_eq!(db.iter(None).unwrap().count(), 0);
+	}
+
+	#[test]
+	fn async_adapter() {
+		use futures::{executor::block_on, stream::StreamExt};
+		use kvdb::{AsyncAdapter, AsyncKeyValueDB};
+
+		let db = AsyncAdapter::new(create(1));
+
+		let mut batch = DBTransaction::new();
+		batch.put(Some(0), b"foo", b"bar");
+		block_on(db.write(batch)).unwrap();
+
+		assert_eq!(block_on(db.get(Some(0), b"foo")).unwrap().unwrap().as_ref(), b"bar");
+		assert!(block_on(db.get(None, b"foo")).unwrap().is_none());
+		assert_eq!(block_on(db.iter(Some(0)).collect::<Vec<_>>()).len(), 1);
+		assert!(block_on(db.iter(Some(1)).collect::<Vec<_>>())[0].is_err());
+	}
+
+	#[test]
+	fn iter_range() {
+		let db = create(0);
+
+		let mut batch = db.transaction();
+		for key in &[b"a", b"b", b"c", b"d"] {
+			batch.put(None, *key, *key);
+		}
+		db.write(batch).unwrap();
+
+		let keys = |start, end, direction| db.iter_range(None, start, end, direction)
+			.unwrap()
+			.map(|(k, _)| k.into_vec())
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			keys(Bound::Included(&b"b"[..]), Bound::Excluded(&b"d"[..]), Direction::Forward),
+			vec![b"b".to_vec(), b"c".to_vec()],
+		);
+		assert_eq!(
+			keys(Bound::Excluded(&b"a"[..]), Bound::Included(&b"c"[..]), Direction::Reverse),
+			vec![b"c".to_vec(), b"b".to_vec()],
+		);
+		assert_eq!(
+			keys(Bound::Unbounded, Bound::Included(&b"bb"[..]), Direction::Reverse),
+			vec![b"b".to_vec(), b"a".to_vec()],
+		);
+		assert!(keys(Bound::Excluded(&b"c"[..]), Bound::Excluded(&b"c"[..]), Direction::Forward).is_empty());
+	}
+
+	#[test]
+	fn column_stats() {
+		let db = create(1);
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"foo", b"bar");
+		batch.put(Some(0), b"hello", b"world");
+		db.write(batch).unwrap();
+
+		assert_eq!(db.column_stats(Some(0)).unwrap(), ColumnStats {
+			num_keys: 2,
+			live_data_size: 16,
+			num_sst_files: 0,
+			memtable_size: 16,
+		});
+		assert_eq!(db.column_stats(None).unwrap(), ColumnStats::default());
+		assert!(db.column_stats(Some(1)).is_err());
+	}
+
+	#[test]
+	fn migration() {
+		use kvdb::migration::{self, AddColumn, DropColumn, Manager, RewriteColumn};
+
+		let db = create(1);
+		let mut batch = db.transaction();
+		for i in 0u8..10 {
+			batch.put(Some(0), &[i], &[i]);
+		}
+		db.write(batch).unwrap();
+
+		let mut manager = Manager::new();
+		manager.add_migration(AddColumn { version: 1, column: 1 }).unwrap();
+		manager.add_migration(RewriteColumn {
+			version: 2,
+			from: Some(0),
+			to: Some(1),
+			batch_size: 3,
+			rewrite: |key: &[u8], value: &[u8]| Some((key.to_vec(), vec![value[0] * 2])),
+		}).unwrap();
+		manager.add_migration(RewriteColumn {
+			version: 3,
+			from: Some(0),
+			to: Some(0),
+			batch_size: 4,
+			rewrite: |key: &[u8], _: &[u8]| if key[0] % 2 == 0 { Some((key.to_vec(), vec![])) } else { None },
+		}).unwrap();
+		assert!(manager.add_migration(DropColumn { version: 3, column: 1 }).is_err());
+
+		assert_eq!(migration::version(&db).unwrap(), 0);
+		assert!(manager.is_needed(&db).unwrap());
+		assert_eq!(manager.migrate(&db).unwrap(), 3);
+		assert!(!manager.is_needed(&db).unwrap());
+
+		assert_eq!(db.num_columns(), 2);
+		assert_eq!(db.iter(Some(1)).unwrap().count(), 10);
+		assert_eq!(&*db.get(Some(1), &[7]).unwrap().unwrap(), &[14]);
+		assert_eq!(db.iter(Some(0)).unwrap().count(), 5);
+		assert_eq!(db.get(Some(0), &[4]).unwrap().unwrap().len(), 0);
+		assert!(db.get(None, migration::PROGRESS_KEY).unwrap().is_none());
+
+		// migrations are only run once.
+		assert_eq!(manager.migrate(&db).unwrap(), 3);
+		assert_eq!(db.iter(Some(0)).unwrap().count(), 5);
+
+		let mut manager = Manager::new();
+		manager.add_migration(RewriteColumn {
+			version: 4,
+			from: Some(0),
+			to: Some(1),
+			batch_size: 0,
+			rewrite: |key: &[u8], value: &[u8]| Some((key.to_vec(), value.to_vec())),
+		}).unwrap();
+		match manager.migrate(&db) {
+			Err(kvdb::Error::Migration(ref err)) if err.contains("version 4") => {},
+			_ => panic!("expected a migration error"),
+		}
+		assert_eq!(migration::version(&db).unwrap(), 3);
+
+		// the default column holds the schema version.
+		let mut manager = Manager::new();
+		manager.add_migration(RewriteColumn {
+			version: 4,
+			from: Some(0),
+			to: None,
+			batch_size: 10,
+			rewrite: |key: &[u8], value: &[u8]| Some((key.to_vec(), value.to_vec())),
+		}).unwrap();
+		assert!(manager.migrate(&db).is_err());
+		assert_eq!(migration::version(&db).unwrap(), 3);
+	}
+
+	#[test]
+	fn migration_resumes() {
+		use kvdb::migration::{self, Manager, RewriteColumn};
+
+		let db = create(2);
+		let mut batch = db.transaction();
+		for i in 0u8..10 {
+			batch.put(Some(0), &[i], &[i]);
+		}
+		// an interrupted migration to version 1 which rewrote the keys up to 4.
+		let mut progress = 1u32.to_be_bytes().to_vec();
+		progress.push(4);
+		batch.put(None, migration::PROGRESS_KEY, &progress);
+		db.write(batch).unwrap();
+
+		let mut manager = Manager::new();
+		manager.add_migration(RewriteColumn {
+			version: 1,
+			from: Some(0),
+			to: Some(1),
+			batch_size: 100,
+			rewrite: |key: &[u8], value: &[u8]| Some((key.to_vec(), value.to_vec())),
+		}).unwrap();
+		assert_eq!(manager.migrate(&db).unwrap(), 1);
+
+		let keys: Vec<_> = db.iter(Some(1)).unwrap().map(|(k, _)| k[0]).collect();
+		assert_eq!(keys, vec![5, 6, 7, 8, 9]);
+
+		manager.add_migration(migration::DropColumn { version: 2, column: 1 }).unwrap();
+		assert_eq!(manager.migrate(&db).unwrap(), 2);
+		assert_eq!(db.num_columns(), 1);
+	}
+
+	#[test]
+	fn merge() {
+		let counter: MergeFn = Arc::new(|_key: &[u8], existing: Option<&[u8]>, operands: &[&[u8]]| {
+			let count = existing.map_or(0, |v| v[0]) + operands.iter().map(|op| op[0]).sum::<u8>();
+			vec![count]
+		});
+		let db = create(1).with_merge_operator(Some(0), counter);
+
+		let mut batch = db.transaction();
+		batch.merge(Some(0), b"foo", &[1]);
+		batch.merge(Some(0), b"foo", &[2]);
+		batch.put(Some(0), b"bar", &[5]);
+		batch.merge(Some(0), b"bar", &[1]);
+		db.write(batch).unwrap();
+
+		assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), &[3]);
+		assert_eq!(&*db.get(Some(0), b"bar").unwrap().unwrap(), &[6]);
+
+		// no merge operator registered, nothing is written.
+		let mut batch = db.transaction();
+		batch.merge(Some(0), b"foo", &[1]);
+		batch.merge(None, b"foo", &[1]);
+		assert!(db.write(batch).is_err());
+		assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), &[3]);
+		assert!(db.get(None, b"foo").unwrap().is_none());
+	}
+
+	// A unique file path in the temporary directory, removed with its temporary file on drop.
+	struct TempPath(PathBuf);
+
+	impl TempPath {
+		fn new(name: &str) -> Self {
+			TempPath(std::env::temp_dir().join(format!("kvdb-memorydb-{}-{}", name, std::process::id())))
+		}
+	}
+
+	impl Drop for TempPath {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_file(&self.0);
+		}
+	}
+
+	#[test]
+	fn persistence() {
+		let path = TempPath::new("persistence");
+		{
+			let db = open(&path.0, 1).unwrap();
+			let mut batch = db.transaction();
+			batch.put(None, b"foo", b"bar");
+			batch.put(Some(0), b"baz", b"qux");
+			db.write(batch).unwrap();
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"buffered", b"value");
+			db.write_buffered(batch);
+		}
+
+		let db = open(&path.0, 2).unwrap();
+		assert_eq!(&*db.get(None, b"foo").unwrap().unwrap(), b"bar");
+		assert_eq!(&*db.get(Some(0), b"baz").unwrap().unwrap(), b"qux");
+		assert_eq!(&*db.get(Some(0), b"buffered").unwrap().unwrap(), b"value");
+		assert_eq!(db.num_columns(), 2);
+		assert!(db.get(Some(1), b"foo").unwrap().is_none());
+	}
+
+	#[test]
+	fn persistence_detects_corruption() {
+		let path = TempPath::new("corruption");
+		{
+			let db = open(&path.0, 0).unwrap();
+			let mut batch = db.transaction();
+			batch.put(None, b"foo", b"bar");
+			db.write(batch).unwrap();
+		}
+
+		let mut data = std::fs::read(&path.0).unwrap();
+		let last = data.len() - 9;
+		data[last] ^= 1;
+		std::fs::write(&path.0, &data).unwrap();
+		match open(&path.0, 0) {
+			Err(Error::Corruption(_)) => {},
+			_ => panic!("corruption is detected"),
+		}
+
+		std::fs::write(&path.0, b"garbage").unwrap();
+		match open(&path.0, 0) {
+			Err(Error::Corruption(_)) => {},
+			_ => panic!("corruption is detected"),
+		}
+	}
+
+	#[test]
+	fn restore() {
+		let path = TempPath::new("restore");
+		let backup = TempPath::new("restore-backup");
+		{
+			let db = open(&backup.0, 0).unwrap();
+			let mut batch = db.transaction();
+			batch.put(None, b"foo", b"restored");
+			db.write(batch).unwrap();
+		}
+
+		let db = open(&path.0, 0).unwrap();
+		let mut batch = db.transaction();
+		batch.put(None, b"foo", b"bar");
+		batch.put(None, b"baz", b"qux");
+		db.write(batch).unwrap();
+
+		db.restore(backup.0.to_str().unwrap()).unwrap();
+		assert_eq!(&*db.get(None, b"foo").unwrap().unwrap(), b"restored");
+		assert!(db.get(None, b"baz").unwrap().is_none());
+		assert!(!backup.0.exists());
+		drop(db);
+
+		let db = open(&path.0, 0).unwrap();
+		assert_eq!(&*db.get(None, b"foo").unwrap().unwrap(), b"restored");
+		drop(db);
+
+		// a database whose file can't be replaced is left unchanged.
+		{
+			let db = open(&backup.0, 0).unwrap();
+			let mut batch = db.transaction();
+			batch.put(None, b"foo", b"backup");
+			db.write(batch).unwrap();
+		}
+		let db = open(path.0.join("missing"), 0).unwrap();
+		let mut batch = db.transaction();
+		batch.put(None, b"foo", b"bar");
+		db.write_buffered(batch);
+		assert!(db.restore(backup.0.to_str().unwrap()).is_err());
+		assert_eq!(&*db.get(None, b"foo").unwrap().unwrap(), b"bar");
+		assert!(backup.0.exists());
 	}
 }
@@ -0,0 +1,123 @@
+// Copyright 2015-2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! File format of persistent `InMemory` databases.
+//!
+//! The file starts with `MAGIC`, followed by the number of columns and each column:
+//! its number (`0` for the default column, `n + 1` for column `n`), its number of entries
+//! and the length-prefixed keys and values. The file ends with a checksum of the rest.
+//! Integers are big-endian.
+
+use std::{fs, io::Write, path::Path, sync::Arc, collections::{BTreeMap, HashMap}};
+use kvdb::{DBValue, Error, Result};
+
+use crate::Columns;
+
+const MAGIC: &[u8] = b"kvdbmem\x01";
+
+// FNV-1a hash of the data, detecting truncated and garbled files.
+fn checksum(data: &[u8]) -> u64 {
+	data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x100_0000_01b3))
+}
+
+fn corrupted(what: &str) -> Error {
+	Error::Corruption(format!("Invalid in-memory database file: {}", what))
+}
+
+// Write the columns to a file, replacing it atomically.
+pub(crate) fn save(columns: &Columns, path: &Path) -> Result<()> {
+	let mut data = MAGIC.to_vec();
+	data.extend_from_slice(&(columns.len() as u32).to_be_bytes());
+	let mut cols: Vec<_> = columns.iter().collect();
+	cols.sort_by_key(|&(col, _)| *col);
+	for (col, map) in cols {
+		data.extend_from_slice(&col.map_or(0, |c| c + 1).to_be_bytes());
+		data.extend_from_slice(&(map.len() as u64).to_be_bytes());
+		for (key, value) in map.iter() {
+			data.extend_from_slice(&(key.len() as u32).to_be_bytes());
+			data.extend_from_slice(key);
+			data.extend_from_slice(&(value.len() as u32).to_be_bytes());
+			data.extend_from_slice(value);
+		}
+	}
+	let sum = checksum(&data);
+	data.extend_from_slice(&sum.to_be_bytes());
+
+	let mut tmp_path = path.as_os_str().to_owned();
+	tmp_path.push(".tmp");
+	{
+		let mut file = fs::File::create(&tmp_path)?;
+		file.write_all(&data)?;
+		file.sync_all()?;
+	}
+	fs::rename(&tmp_path, path)?;
+	Ok(())
+}
+
+// Read the columns from a file written by `save`.
+pub(crate) fn load(path: &Path) -> Result<Columns> {
+	let data = fs::read(path)?;
+	if data.len() < MAGIC.len() + 12 || &data[..MAGIC.len()] != MAGIC {
+		return Err(corrupted("unknown format"));
+	}
+	let (data, sum) = data.split_at(data.len() - 8);
+	if checksum(data) != read_u64(&mut &sum[..])? {
+		return Err(corrupted("checksum mismatch"));
+	}
+
+	let mut data = &data[MAGIC.len()..];
+	let mut columns = HashMap::new();
+	for _ in 0..read_u32(&mut data)? {
+		let col = read_u32(&mut data)?.checked_sub(1);
+		let mut map = BTreeMap::new();
+		for _ in 0..read_u64(&mut data)? {
+			let key = read_slice(&mut data)?.to_vec();
+			let value = DBValue::from_slice(read_slice(&mut data)?);
+			map.insert(key, value);
+		}
+		columns.insert(col, Arc::new(map));
+	}
+	if !data.is_empty() {
+		return Err(corrupted("trailing data"));
+	}
+	Ok(columns)
+}
+
+fn read_bytes<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+	if data.len() < len {
+		return Err(corrupted("unexpected end of file"));
+	}
+	let (bytes, rest) = data.split_at(len);
+	*data = rest;
+	Ok(bytes)
+}
+
+fn read_u32(data: &mut &[u8]) -> Result<u32> {
+	let mut bytes = [0u8; 4];
+	bytes.copy_from_slice(read_bytes(data, 4)?);
+	Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u64(data: &mut &[u8]) -> Result<u64> {
+	let mut bytes = [0u8; 8];
+	bytes.copy_from_slice(read_bytes(data, 8)?);
+	Ok(u64::from_be_bytes(bytes))
+}
+
+fn read_slice<'a>(data: &mut &'a [u8]) -> Result<&'a [u8]> {
+	let len = read_u32(data)? as usize;
+	read_bytes(data, len)
+}
@@ -0,0 +1,117 @@
+// Copyright 2015-2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use kvdb::{Error, Result};
+
+use crate::{col_config, rocksdb_err, Database};
+use crate::rocksdb::{Options, SstFileWriter};
+
+// SST file of a single column being written.
+struct ColumnFile {
+	writer: SstFileWriter,
+	path: PathBuf,
+	last_key: Option<Vec<u8>>,
+}
+
+/// Imports large amounts of sorted data into a `Database`, bypassing the write buffer.
+///
+/// The data of each column is written to an SST file next to the database,
+/// which is ingested into the database by `finish`. Keys must be added in strictly
+/// increasing order within each column. Ingested values replace any existing ones.
+pub struct BulkLoader<'a> {
+	db: &'a Database,
+	dir: PathBuf,
+	files: HashMap<Option<u32>, ColumnFile>,
+}
+
+impl<'a> BulkLoader<'a> {
+	pub(crate) fn new(db: &'a Database) -> Result<Self> {
+		db.check_writable()?;
+		let dir = PathBuf::from(format!("{}.bulk", db.path));
+		if dir.exists() {
+			fs::remove_dir_all(&dir)?;
+		}
+		fs::create_dir_all(&dir)?;
+		Ok(BulkLoader {
+			db,
+			dir,
+			files: HashMap::new(),
+		})
+	}
+
+	/// Add a key/value pair to a column.
+	/// Fails if the key is not greater than the previous key added to the column.
+	pub fn put(&mut self, col: Option<u32>, key: &[u8], value: &[u8]) -> Result<()> {
+		if !self.files.contains_key(&col) {
+			let file = self.create_file(col)?;
+			self.files.insert(col, file);
+		}
+		let file = self.files.get_mut(&col).expect("file was inserted above; qed");
+		if file.last_key.as_ref().map_or(false, |last| key <= &last[..]) {
+			return Err(Error::Backend("Keys must be added to a bulk loader in increasing order".into()));
+		}
+		file.writer.put(key, value).map_err(rocksdb_err)?;
+		file.last_key = Some(key.to_vec());
+		Ok(())
+	}
+
+	fn create_file(&self, col: Option<u32>) -> Result<ColumnFile> {
+		// the files must be written with the options of the column they are ingested into.
+		match *self.db.db.read() {
+			Some(ref db) => db.cf(col)?,
+			None => return Err(Error::Closed),
+		};
+		let opts = match col {
+			Some(c) => col_config(&self.db.config, c, &self.db.block_opts)?,
+			None => Options::new(),
+		};
+		let path = self.dir.join(match col {
+			Some(c) => format!("col{}.sst", c),
+			None => "default.sst".to_owned(),
+		});
+		let mut writer = SstFileWriter::create(opts);
+		writer.open(&path).map_err(rocksdb_err)?;
+		Ok(ColumnFile {
+			writer,
+			path,
+			last_key: None,
+		})
+	}
+
+	/// Finish writing the SST files and ingest them into the database.
+	///
+	/// Changes buffered in the database are flushed first, so they are replaced by the ingested data.
+	pub fn finish(mut self) -> Result<()> {
+		self.db.flush()?;
+		for (col, mut file) in self.files.drain() {
+			file.writer.finish().map_err(rocksdb_err)?;
+			match *self.db.db.read() {
+				Some(ref db) => db.db.ingest_external_files(db.cf(col)?, &[&file.path], true).map_err(rocksdb_err)?,
+				None => return Err(Error::Closed),
+			}
+		}
+		Ok(())
+	}
+}
+
+impl<'a> Drop for BulkLoader<'a> {
+	fn drop(&mut self) {
+		// ignore errors
+		let _ = fs::remove_dir_all(&self.dir);
+	}
+}
@@ -14,22 +14,38 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+mod bulk;
+mod rocksdb;
+mod stats;
+
+// links librocksdb, which `rocksdb` binds to.
+extern crate parity_rocksdb_sys;
+
 use std::{
-	cmp, fs, io, mem, result, error,
-	collections::HashMap, marker::PhantomData, path::Path
+	cmp, fs, iter, mem, result,
+	io::Write,
+	collections::{HashMap, HashSet}, marker::PhantomData, ops::Bound, path::Path,
+	sync::{Arc, atomic::{AtomicBool, AtomicU64, Ordering}}, time::Duration,
 };
 
-use parking_lot::{Mutex, MutexGuard, RwLock};
-use parity_rocksdb::{
-	DB, Writable, WriteBatch, WriteOptions, IteratorMode, DBIterator,
-	Options, BlockBasedOptions, Direction, Cache, Column, ReadOptions
+use parking_lot::{Mutex, MutexGuard, RwLock, RwLockUpgradableReadGuard};
+use crate::rocksdb::{
+	DB, WriteBatch, WriteOptions, IteratorMode, Options, BlockBasedOptions, Direction, Cache, Column,
+	ReadOptions, DBIterator, Snapshot, BackupEngine,
 };
-use interleaved_ordered::{interleave_ordered, InterleaveOrdered};
+use interleaved_ordered::interleave_ordered;
 
-use log::{debug, warn};
+use log::{debug, info, warn};
 use elastic_array::ElasticArray32;
 use fs_swap::{swap, swap_nonatomic};
-use kvdb::{KeyValueDB, DBTransaction, DBValue, DBOp};
+use kvdb::{
+	KeyValueDB, KeyValueDBSnapshot, DBTransaction, DBValue, DBOp, ColumnId, ColumnStats, Durability, MergeFn,
+	Error, Result,
+};
+use kvdb::migration::ColumnManager;
+
+pub use bulk::BulkLoader;
+pub use stats::IoStats;
 
 #[cfg(target_os = "linux")]
 use regex::Regex;
@@ -40,17 +56,158 @@ use std::fs::File;
 #[cfg(target_os = "linux")]
 use std::path::PathBuf;
 
-fn other_io_err<E>(e: E) -> io::Error where E: Into<Box<dyn error::Error + Send + Sync>> {
-	io::Error::new(io::ErrorKind::Other, e)
+// Convert an error message reported by RocksDB.
+fn rocksdb_err<E: Into<String>>(e: E) -> Error {
+	let e = e.into();
+	if e.starts_with("Corruption:") {
+		Error::Corruption(e)
+	} else {
+		Error::Backend(e)
+	}
 }
 
 const KB: usize = 1024;
 const MB: usize = 1024 * KB;
 const DB_DEFAULT_MEMORY_BUDGET_MB: usize = 128;
+// Size of the batches written when migrating columns.
+const MIGRATION_BATCH_SIZE: usize = 16 * MB;
+// Number of LSM levels RocksDB is configured with by default.
+const NUM_LEVELS: usize = 7;
+
+// A key and its value, as returned by the iterators.
+type KeyValuePair = (Box<[u8]>, Box<[u8]>);
 
+#[derive(Clone)]
 enum KeyState {
 	Insert(DBValue),
 	Delete,
+	// Merge operands to apply to the value written before.
+	Merge(Vec<DBValue>),
+}
+
+// Changes to a single column buffered with `write_buffered`.
+#[derive(Default, Clone)]
+struct ColumnOverlay {
+	keys: HashMap<ElasticArray32<u8>, KeyState>,
+	// Prefixes deleted with `DBOp::DeletePrefix`. These are written before `keys` on flush,
+	// so that keys inserted after the deletion are preserved.
+	deleted_prefixes: Vec<ElasticArray32<u8>>,
+}
+
+impl ColumnOverlay {
+	// Get the buffered state of a key, taking deleted prefixes into account.
+	fn get(&self, key: &[u8]) -> Option<&KeyState> {
+		match self.keys.get(key) {
+			Some(state) => Some(state),
+			None if self.is_deleted_by_prefix(key) => Some(&KeyState::Delete),
+			None => None,
+		}
+	}
+
+	fn is_deleted_by_prefix(&self, key: &[u8]) -> bool {
+		self.deleted_prefixes.iter().any(|prefix| key.starts_with(prefix))
+	}
+
+	fn delete_prefix(&mut self, prefix: ElasticArray32<u8>) {
+		self.keys.retain(|k, _| !k.starts_with(&prefix));
+		self.deleted_prefixes.push(prefix);
+	}
+
+	// Merge operands into the buffered value of a key. Operands are kept until flush
+	// if the key has no buffered value.
+	fn merge(&mut self, key: ElasticArray32<u8>, mut operands: Vec<DBValue>, merge: &MergeFn) {
+		let existing = match self.get(&key) {
+			Some(&KeyState::Insert(ref value)) => Some(value),
+			Some(&KeyState::Delete) => None,
+			Some(&KeyState::Merge(_)) | None => {
+				match self.keys.entry(key).or_insert_with(|| KeyState::Merge(Vec::new())) {
+					KeyState::Merge(ref mut buffered) => buffered.append(&mut operands),
+					_ => unreachable!("the key has buffered merge operands or no state; qed"),
+				}
+				return;
+			},
+		};
+		let operands: Vec<&[u8]> = operands.iter().map(|op| &op[..]).collect();
+		let value = merge(&key, existing.map(|v| &v[..]), &operands);
+		self.keys.insert(key, KeyState::Insert(DBValue::from_vec(value)));
+	}
+
+	// Apply changes which were buffered after the ones in `self`.
+	fn extend(&mut self, newer: &ColumnOverlay, merge: Option<&MergeFn>) {
+		for prefix in &newer.deleted_prefixes {
+			self.delete_prefix(prefix.clone());
+		}
+		for (key, state) in newer.keys.iter() {
+			match (state, merge) {
+				(KeyState::Merge(ref operands), Some(merge)) => self.merge(key.clone(), operands.clone(), merge),
+				_ => {
+					self.keys.insert(key.clone(), state.clone());
+				},
+			}
+		}
+	}
+}
+
+// Read a key through buffered changes, newest first, falling back to RocksDB.
+// Returns the value and whether it was read without touching RocksDB.
+fn read_through(
+	merge: Option<&MergeFn>,
+	db: &DBAndColumns,
+	col: Option<u32>,
+	key: &[u8],
+	layers: &[&ColumnOverlay],
+	read_opts: &ReadOptions,
+) -> Result<(Option<DBValue>, bool)> {
+	let (layer, older) = match layers.split_first() {
+		Some(split) => split,
+		None => return Ok((db.get_opt(col, key, read_opts)?, false)),
+	};
+	match layer.get(key) {
+		Some(&KeyState::Insert(ref value)) => Ok((Some(value.clone()), true)),
+		Some(&KeyState::Delete) => Ok((None, true)),
+		Some(&KeyState::Merge(ref operands)) => {
+			let merge = merge.expect("merges are only buffered for columns with a merge operator; qed");
+			let (existing, cached) = read_through(Some(merge), db, col, key, older, read_opts)?;
+			let operands: Vec<&[u8]> = operands.iter().map(|op| &op[..]).collect();
+			let value = merge(key, existing.as_ref().map(|v| &v[..]), &operands);
+			Ok((Some(DBValue::from_vec(value)), cached))
+		},
+		None => read_through(merge, db, col, key, older, read_opts),
+	}
+}
+
+/// Compression algorithm used for SST files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compression {
+	/// No compression.
+	None,
+	/// Snappy compression.
+	Snappy,
+	/// LZ4 compression. Not built into RocksDB, opening a database with it fails.
+	Lz4,
+	/// Zstandard compression with the given compression level.
+	/// Not built into RocksDB, opening a database with it fails.
+	Zstd(i32),
+}
+
+impl Compression {
+	// Name of the algorithm in RocksDB options, `None` if RocksDB is built without it.
+	fn as_option_str(&self) -> Option<&'static str> {
+		match *self {
+			Compression::None => Some("kNoCompression"),
+			Compression::Snappy => Some("kSnappyCompression"),
+			Compression::Lz4 | Compression::Zstd(_) => None,
+		}
+	}
+}
+
+// Get the RocksDB option for compressing each level with the given algorithm.
+// The last algorithm applies to all the remaining levels.
+fn compression_options(levels: &[Compression]) -> Result<String> {
+	let names = levels.iter()
+		.map(|c| c.as_option_str().ok_or(Error::Unsupported("Only Snappy compression is built into RocksDB")))
+		.collect::<Result<Vec<_>>>()?;
+	Ok(format!("compression_per_level={}", names.join(":")))
 }
 
 /// Compaction profile for the database settings
@@ -62,6 +219,8 @@ pub struct CompactionProfile {
 	pub block_size: usize,
 	/// rate limiter for background flushes and compactions, bytes/sec, if any
 	pub write_rate_limit: Option<u64>,
+	/// compression of all levels, unless overridden per column
+	pub compression: Compression,
 }
 
 impl Default for CompactionProfile {
@@ -132,6 +291,7 @@ impl CompactionProfile {
 			initial_file_size: 64 * MB as u64,
 			block_size: 16 * KB,
 			write_rate_limit: None,
+			compression: Compression::None,
 		}
 	}
 
@@ -141,10 +301,46 @@ impl CompactionProfile {
 			initial_file_size: 256 * MB as u64,
 			block_size: 64 * KB,
 			write_rate_limit: Some(16 * MB as u64),
+			compression: Compression::None,
 		}
 	}
 }
 
+/// Configuration of a single column, overriding the database-wide settings.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct ColumnConfig {
+	/// Memory budget (in MiB) used for setting block cache size, write buffer size of the column.
+	/// The column gets its own block cache when set.
+	pub memory_budget: Option<usize>,
+	/// Block size, overrides the compaction profile. The column gets its own block cache when set.
+	pub block_size: Option<usize>,
+	/// Bits per key of the bloom filter, no filter if `None`.
+	pub bloom_filter_bits: Option<u32>,
+	/// Length of the key prefix used by the prefix extractor, if any.
+	pub prefix_extractor_len: Option<usize>,
+	/// Compression of each level, starting from L0. The last entry applies to the remaining levels.
+	/// Overrides the compaction profile.
+	pub compression_per_level: Option<Vec<Compression>>,
+}
+
+impl ColumnConfig {
+	// Whether the column gets a block cache of its own instead of the shared one.
+	fn has_own_cache(&self) -> bool {
+		self.memory_budget.is_some() || self.block_size.is_some()
+	}
+}
+
+/// The way a database is opened.
+#[derive(Clone, PartialEq, Debug)]
+pub enum OpenMode {
+	/// Open for reading and writing, creating the database if it doesn't exist.
+	ReadWrite,
+	/// Open read-only. The database must exist and its columns are neither created nor
+	/// migrated. It can be opened while another process writes to it, but doesn't see
+	/// the writes made after it is opened.
+	ReadOnly,
+}
+
 /// Database configuration
 #[derive(Clone)]
 pub struct DatabaseConfig {
@@ -156,6 +352,26 @@ pub struct DatabaseConfig {
 	pub compaction: CompactionProfile,
 	/// Set number of columns
 	pub columns: Option<u32>,
+	/// Per-column configuration overrides, by column.
+	pub column_config: HashMap<Option<u32>, ColumnConfig>,
+	/// Open mode. Writing to a database opened with `OpenMode::ReadOnly` fails with
+	/// `Error::ReadOnly`. Transactions passed to `Database::write_buffered` are discarded,
+	/// and the next `Database::flush` fails instead.
+	pub open_mode: OpenMode,
+	/// Names of the columns, by column index. Columns without a name are called `col<index>`.
+	pub column_names: Vec<String>,
+	/// Durability of writes, unless overridden by the transaction.
+	pub durability: Durability,
+	/// Use fsync instead of fdatasync when syncing files.
+	pub use_fsync: bool,
+	/// Merge functions applied to `DBOp::Merge` operations, by column. They are registered as
+	/// the RocksDB merge operators of the columns, which store the operands and apply them when
+	/// reading and compacting, so a column with merges written to it needs its function on every open.
+	/// Transactions merging into a column without one fail on `write`, and are discarded by
+	/// `write_buffered`, which makes the next `flush` fail.
+	pub merge_operators: HashMap<Option<u32>, MergeFn>,
+	/// Collect RocksDB statistics, reported by `Database::io_stats`. Slows reads and writes down a bit.
+	pub enable_statistics: bool,
 }
 
 impl DatabaseConfig {
@@ -167,12 +383,52 @@ impl DatabaseConfig {
 		config
 	}
 
+	/// Create new `DatabaseConfig` with default parameters and a column for each of the given names.
+	///
+	/// Existing columns named by index (`col0`, `col1`, ...) are migrated to the given names on open.
+	pub fn with_named_columns(names: &[&str]) -> Self {
+		let mut config = Self::with_columns(Some(names.len() as u32));
+		config.column_names = names.iter().map(|name| name.to_string()).collect();
+		config
+	}
+
+	/// Name of the column family of the given column.
+	pub fn column_name(&self, col: u32) -> String {
+		self.column_names.get(col as usize).cloned().unwrap_or_else(|| numbered_column_name(col))
+	}
+
 	pub fn memory_budget(&self) -> usize {
 		self.memory_budget.unwrap_or(DB_DEFAULT_MEMORY_BUDGET_MB) * MB
 	}
 
+	/// Memory budget of the columns without a budget of their own, in bytes.
+	/// The budgets of the other columns are taken from the database budget first.
 	pub fn memory_budget_per_col(&self) -> usize {
-		self.memory_budget() / self.columns.unwrap_or(1) as usize
+		let columns: Vec<Option<u32>> = match self.columns {
+			Some(columns) => (0..columns).map(Some).collect(),
+			None => vec![None],
+		};
+		let overridden: Vec<usize> = columns.iter()
+			.filter_map(|col| self.column_config.get(col).and_then(|c| c.memory_budget))
+			.collect();
+		let remaining = self.memory_budget().saturating_sub(overridden.iter().sum::<usize>() * MB);
+		remaining / cmp::max(1, columns.len() - overridden.len())
+	}
+
+	/// Memory budget of the given column, in bytes.
+	pub fn memory_budget_for_col(&self, col: Option<u32>) -> usize {
+		self.column_config.get(&col)
+			.and_then(|c| c.memory_budget)
+			.map_or_else(|| self.memory_budget_per_col(), |budget| budget * MB)
+	}
+
+	// Whether the column uses the block cache shared by the columns.
+	// The default column only does if it is configured.
+	fn uses_shared_cache(&self, col: Option<u32>) -> bool {
+		match self.column_config.get(&col) {
+			Some(column) => !column.has_own_cache(),
+			None => col.is_some(),
+		}
 	}
 }
 
@@ -183,6 +439,13 @@ impl Default for DatabaseConfig {
 			memory_budget: None,
 			compaction: CompactionProfile::default(),
 			columns: None,
+			column_config: HashMap::new(),
+			open_mode: OpenMode::ReadWrite,
+			column_names: Vec::new(),
+			durability: Durability::Wal,
+			use_fsync: false,
+			merge_operators: HashMap::new(),
+			enable_statistics: false,
 		}
 	}
 }
@@ -192,7 +455,7 @@ impl Default for DatabaseConfig {
 // inner DB (to prevent closing via restoration) may be re-evaluated in the future.
 //
 pub struct DatabaseIterator<'a> {
-	iter: InterleaveOrdered<::std::vec::IntoIter<(Box<[u8]>, Box<[u8]>)>, DBIterator>,
+	iter: Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>,
 	_marker: PhantomData<&'a Database>,
 }
 
@@ -204,51 +467,207 @@ impl<'a> Iterator for DatabaseIterator<'a> {
 	}
 }
 
+/// A read-only view of a `Database` at the point in time it was taken.
+///
+/// Includes changes which were buffered at that time, and keeps reading the columns the
+/// database had. The snapshot keeps the RocksDB database open until it is dropped, even if
+/// the `Database` is closed or columns are dropped meanwhile.
+pub struct DatabaseSnapshot<'a> {
+	// Buffered changes at the time the snapshot was taken.
+	overlay: Vec<ColumnOverlay>,
+	snapshot: Snapshot,
+	db: Arc<DBAndColumns>,
+	merge_operators: &'a HashMap<Option<u32>, MergeFn>,
+}
+
+impl<'a> DatabaseSnapshot<'a> {
+	// Iterate over the keys from `from` on, with the buffered changes applied.
+	fn iter_from<'b>(&'b self, col: Option<u32>, from: &'b [u8])
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'b>>
+	{
+		let read_opts = self.snapshot.read_options();
+		let iter = self.db.iterator_opt(col, IteratorMode::From(from, Direction::Forward), read_opts)?;
+		let overlay = &self.overlay[Database::to_overlay_column(col)];
+		let mut overlay_data = Vec::new();
+		for (k, v) in overlay.keys.iter().filter(|&(k, _)| &k[..] >= from) {
+			let value = match *v {
+				KeyState::Insert(ref value) => Some(value.clone()),
+				KeyState::Delete => None,
+				KeyState::Merge(_) =>
+					read_through(self.merge_operators.get(&col), &self.db, col, k, &[overlay], read_opts)?.0,
+			};
+			if let Some(value) = value {
+				overlay_data.push((k.clone().into_vec().into_boxed_slice(), value.into_vec().into_boxed_slice()));
+			}
+		}
+		overlay_data.sort();
+
+		// the buffered state of a key, deleted or not, replaces the one in RocksDB.
+		let iter = iter.filter(move |(k, _)| overlay.get(k).is_none());
+		Ok(Box::new(interleave_ordered(overlay_data, iter)))
+	}
+}
+
+impl<'a> KeyValueDBSnapshot for DatabaseSnapshot<'a> {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>> {
+		// make sure the column exists before looking into the overlay.
+		self.db.cf(col)?;
+		let overlay = &self.overlay[Database::to_overlay_column(col)];
+		read_through(self.merge_operators.get(&col), &self.db, col, key, &[overlay], self.snapshot.read_options())
+			.map(|(value, _)| value)
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Result<Option<Box<[u8]>>> {
+		match self.iter_from_prefix(col, prefix)?.next() {
+			Some((k, v)) => Ok(if k[0 .. prefix.len()] == prefix[..] { Some(v) } else { None }),
+			_ => Ok(None)
+		}
+	}
+
+	fn iter<'b>(&'b self, col: Option<u32>) -> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'b>> {
+		self.iter_from(col, &[])
+	}
+
+	fn iter_from_prefix<'b>(&'b self, col: Option<u32>, prefix: &'b [u8])
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'b>>
+	{
+		self.iter_from(col, prefix)
+	}
+}
+
+// The database with the handles of its columns. Replaced when columns are added or dropped,
+// so that snapshots keep the handles they were taken with.
+#[derive(Clone)]
 struct DBAndColumns {
 	db: DB,
 	cfs: Vec<Column>,
+	// The options the database was opened with, which collect its statistics.
+	opts: Arc<Options>,
+}
+
+impl DBAndColumns {
+	// Open the database with the given column families.
+	fn open(opts: &Arc<Options>, path: &str, cfnames: &[&str], cf_options: &[Options]) -> result::Result<DBAndColumns, String> {
+		DB::open(opts, path, cfnames, cf_options).map(|(db, cfs)| DBAndColumns { db, cfs, opts: opts.clone() })
+	}
+
+	// Open the existing database with the given column families for reading only.
+	fn open_for_read_only(opts: &Arc<Options>, path: &str, cfnames: &[&str], cf_options: &[Options]) -> result::Result<DBAndColumns, String> {
+		DB::open_for_read_only(opts, path, cfnames, cf_options).map(|(db, cfs)| DBAndColumns { db, cfs, opts: opts.clone() })
+	}
+
+	// Get the column family handle of a column.
+	fn cf(&self, col: Option<u32>) -> Result<&Column> {
+		match col {
+			None => Ok(self.db.default_cf()),
+			Some(c) => self.cfs.get(c as usize).ok_or(Error::UnknownColumn(col)),
+		}
+	}
+
+	fn get_opt(&self, col: Option<u32>, key: &[u8], read_opts: &ReadOptions) -> Result<Option<DBValue>> {
+		let value = self.db.get(self.cf(col)?, key, read_opts).map_err(rocksdb_err)?;
+		Ok(value.map(DBValue::from_vec))
+	}
+
+	fn iterator_opt(&self, col: Option<u32>, mode: IteratorMode, read_opts: &ReadOptions) -> Result<DBIterator> {
+		Ok(self.db.iterator(self.cf(col)?, mode, read_opts))
+	}
+
+	// Get an integer property of a column, `0` if RocksDB doesn't report it.
+	fn property(&self, cf: &Column, name: &str) -> u64 {
+		self.db.property_int(cf, name)
+	}
+
+	// Whether RocksDB snapshots taken with `Database::snapshot` are alive.
+	fn has_snapshots(&self) -> bool {
+		self.property(self.db.default_cf(), "rocksdb.num-snapshots") > 0
+	}
 }
 
 // get column family configuration from database config.
-fn col_config(config: &DatabaseConfig, block_opts: &BlockBasedOptions) -> io::Result<Options> {
+fn col_config(config: &DatabaseConfig, col: u32, block_opts: &BlockBasedOptions) -> Result<Options> {
 	let mut opts = Options::new();
+	set_column_options(&mut opts, config, Some(col), block_opts)?;
+	Ok(opts)
+}
+
+// Set the options of a column family from the database config.
+fn set_column_options(opts: &mut Options, config: &DatabaseConfig, col: Option<u32>, block_opts: &BlockBasedOptions) -> Result<()> {
+	let column = config.column_config.get(&col).cloned().unwrap_or_default();
+
+	opts.set_parsed_options("level_compaction_dynamic_level_bytes=true").map_err(rocksdb_err)?;
 
-	opts.set_parsed_options("level_compaction_dynamic_level_bytes=true").map_err(other_io_err)?;
+	if column.has_own_cache() {
+		let mut column_block_opts = BlockBasedOptions::new();
+		column_block_opts.set_block_size(column.block_size.unwrap_or(config.compaction.block_size));
+		column_block_opts.set_cache(Cache::new(config.memory_budget_for_col(col) / 3));
+		opts.set_block_based_table_factory(&column_block_opts);
+	} else {
+		opts.set_block_based_table_factory(block_opts);
+	}
 
-	opts.set_block_based_table_factory(block_opts);
+	let mut table_options = vec![
+		"cache_index_and_filter_blocks=true".to_owned(),
+		"pin_l0_filter_and_index_blocks_in_cache=true".to_owned(),
+	];
+	if let Some(bits) = column.bloom_filter_bits {
+		table_options.push(format!("filter_policy=bloomfilter:{}:false", bits));
+	}
+	opts.set_parsed_options(&format!("block_based_table_factory={{{}}}", table_options.join(";")))
+		.map_err(rocksdb_err)?;
 
-	opts.set_parsed_options(
-		&format!("block_based_table_factory={{{};{}}}",
-				 "cache_index_and_filter_blocks=true",
-				 "pin_l0_filter_and_index_blocks_in_cache=true")).map_err(other_io_err)?;
+	if let Some(len) = column.prefix_extractor_len {
+		opts.set_parsed_options(&format!("prefix_extractor=rocksdb.FixedPrefix.{}", len)).map_err(rocksdb_err)?;
+	}
 
-	opts.optimize_level_style_compaction(config.memory_budget_per_col() as i32);
+	opts.optimize_level_style_compaction(config.memory_budget_for_col(col));
 	opts.set_target_file_size_base(config.compaction.initial_file_size);
 
-	opts.set_parsed_options("compression_per_level=").map_err(other_io_err)?;
+	let compression = column.compression_per_level.unwrap_or_else(|| vec![config.compaction.compression]);
+	opts.set_parsed_options(&compression_options(&compression)?).map_err(rocksdb_err)?;
 
-	Ok(opts)
+	set_merge_operator(opts, config, col);
+	Ok(())
+}
+
+// Register the merge function of a column as the merge operator of its options.
+fn set_merge_operator(opts: &mut Options, config: &DatabaseConfig, col: Option<u32>) {
+	if let Some(merge) = config.merge_operators.get(&col) {
+		let merge = merge.clone();
+		opts.set_merge_operator(move |key, existing, operands| merge(key, existing, operands));
+	}
 }
 
 /// Key-Value database.
 pub struct Database {
-	db: RwLock<Option<DBAndColumns>>,
+	// Shared with the snapshots taken of the database.
+	db: RwLock<Option<Arc<DBAndColumns>>>,
 	config: DatabaseConfig,
-	write_opts: WriteOptions,
 	read_opts: ReadOptions,
 	block_opts: BlockBasedOptions,
 	path: String,
 	// Dirty values added with `write_buffered`. Cleaned on `flush`.
-	overlay: RwLock<Vec<HashMap<ElasticArray32<u8>, KeyState>>>,
+	overlay: RwLock<Vec<ColumnOverlay>>,
+	// Strongest durability of the transactions in `overlay`.
+	overlay_durability: Mutex<Option<Durability>>,
 	// Values currently being flushed. Cleared when `flush` completes.
-	flushing: RwLock<Vec<HashMap<ElasticArray32<u8>, KeyState>>>,
+	flushing: RwLock<Vec<ColumnOverlay>>,
 	// Prevents concurrent flushes.
 	// Value indicates if a flush is in progress.
 	flushing_lock: Mutex<bool>,
+	// Sequence number of the last transaction added with `write_buffered`.
+	sequence: AtomicU64,
+	// Sequence number of the last transaction written to RocksDB by `flush`.
+	flushed_sequence: AtomicU64,
+	// Whether automatic compactions are paused with `pause_compactions`.
+	compactions_paused: AtomicBool,
+	// Why `write_buffered` discarded transactions since the last flush, reported by the next one.
+	discarded_writes: Mutex<Option<Error>>,
+	stats: stats::RunningDbStats,
 }
 
 #[inline]
-fn check_for_corruption<T, P: AsRef<Path>>(path: P, res: result::Result<T, String>) -> io::Result<T> {
+fn check_for_corruption<T, P: AsRef<Path>>(path: P, res: result::Result<T, String>) -> Result<T> {
 	if let Err(ref s) = res {
 		if s.starts_with("Corruption:") {
 			warn!("DB corrupted: {}. Repair will be triggered on next restart", s);
@@ -256,35 +675,91 @@ fn check_for_corruption<T, P: AsRef<Path>>(path: P, res: result::Result<T, Strin
 		}
 	}
 
-	res.map_err(other_io_err)
+	res.map_err(rocksdb_err)
+}
+
+fn write_options(durability: Durability) -> WriteOptions {
+	let mut opts = WriteOptions::new();
+	opts.disable_wal(durability == Durability::NoWal);
+	opts.set_sync(durability == Durability::WalSynced);
+	opts
+}
+
+// Get the value of a ticker from RocksDB statistics, which report it as `<name> COUNT : <value>`.
+fn ticker(statistics: &str, name: &str) -> u64 {
+	statistics.lines()
+		.find_map(|line| {
+			let mut words = line.split_whitespace();
+			if words.next() == Some(name) && words.next() == Some("COUNT") && words.next() == Some(":") {
+				words.next().and_then(|value| value.parse().ok())
+			} else {
+				None
+			}
+		})
+		.unwrap_or(0)
 }
 
 fn is_corrupted(s: &str) -> bool {
-	s.starts_with("Corruption:") || s.starts_with("Invalid argument: You have to open all column families")
+	s.starts_with("Corruption:")
+}
+
+// The name of a column in databases created before the columns were named.
+fn numbered_column_name(col: u32) -> String {
+	format!("col{}", col)
+}
+
+// The column families, with their columns, of a database during the migration of the numbered
+// columns: the columns before `col` are migrated, and `col` has its old and/or new name.
+fn migration_cfnames(config: &DatabaseConfig, col: u32, old: bool, new: bool) -> Vec<(String, u32)> {
+	let mut cfnames = Vec::new();
+	for c in 0..config.columns.unwrap_or(0) {
+		let (old_name, new_name) = (numbered_column_name(c), config.column_name(c));
+		// unnamed columns keep their name.
+		if old_name == new_name {
+			cfnames.push((new_name, c));
+			continue;
+		}
+		if c < col || (c == col && new) {
+			cfnames.push((new_name, c));
+		}
+		if c > col || (c == col && old) {
+			cfnames.push((old_name, c));
+		}
+	}
+	cfnames
 }
 
 impl Database {
 	const CORRUPTION_FILE_NAME: &'static str = "CORRUPTED";
+	const MIGRATION_FILE_NAME: &'static str = "MIGRATING_COLUMNS";
 
 	/// Open database with default settings.
-	pub fn open_default(path: &str) -> io::Result<Database> {
+	pub fn open_default(path: &str) -> Result<Database> {
 		Database::open(&DatabaseConfig::default(), path)
 	}
 
-	/// Open database file. Creates if it does not exist.
-	pub fn open(config: &DatabaseConfig, path: &str) -> io::Result<Database> {
+	/// Open database file. Creates if it does not exist and it is opened with `OpenMode::ReadWrite`.
+	pub fn open(config: &DatabaseConfig, path: &str) -> Result<Database> {
+		Self::open_inner(config, path, false)
+	}
+
+	// Open the database, with automatic compactions disabled if they are paused.
+	fn open_inner(config: &DatabaseConfig, path: &str, compactions_paused: bool) -> Result<Database> {
 		let mut opts = Options::new();
 
 		if let Some(rate_limit) = config.compaction.write_rate_limit {
-			opts.set_parsed_options(&format!("rate_limiter_bytes_per_sec={}", rate_limit)).map_err(other_io_err)?;
+			opts.set_parsed_options(&format!("rate_limiter_bytes_per_sec={}", rate_limit)).map_err(rocksdb_err)?;
 		}
-		opts.set_use_fsync(false);
-		opts.create_if_missing(true);
+		opts.set_use_fsync(config.use_fsync);
+		opts.create_if_missing(config.open_mode == OpenMode::ReadWrite);
 		opts.set_max_open_files(config.max_open_files);
-		opts.set_parsed_options("keep_log_file_num=1").map_err(other_io_err)?;
-		opts.set_parsed_options("bytes_per_sync=1048576").map_err(other_io_err)?;
+		opts.set_parsed_options("keep_log_file_num=1").map_err(rocksdb_err)?;
+		opts.set_parsed_options("bytes_per_sync=1048576").map_err(rocksdb_err)?;
 		opts.set_db_write_buffer_size(config.memory_budget_per_col() / 2);
 		opts.increase_parallelism(cmp::max(1, ::num_cpus::get() as i32 / 2));
+		if config.enable_statistics {
+			opts.enable_statistics();
+		}
 
 		let mut block_opts = BlockBasedOptions::new();
 
@@ -297,90 +772,194 @@ impl Database {
 			block_opts.set_cache(cache);
 		}
 
+		if config.column_config.contains_key(&None) {
+			set_column_options(&mut opts, config, None, &block_opts)?;
+		} else {
+			set_merge_operator(&mut opts, config, None);
+		}
+
 		// attempt database repair if it has been previously marked as corrupted
 		let db_corrupted = Path::new(path).join(Database::CORRUPTION_FILE_NAME);
-		if db_corrupted.exists() {
+		if db_corrupted.exists() && config.open_mode == OpenMode::ReadWrite {
 			warn!("DB has been previously marked as corrupted, attempting repair");
-			DB::repair(&opts, path).map_err(other_io_err)?;
+			DB::repair(&opts, path).map_err(rocksdb_err)?;
 			fs::remove_file(db_corrupted)?;
 		}
 
 		let columns = config.columns.unwrap_or(0) as usize;
 
 		let mut cf_options = Vec::with_capacity(columns);
-		let cfnames: Vec<_> = (0..columns).map(|c| format!("col{}", c)).collect();
+		let cfnames: Vec<_> = (0..columns as u32).map(|c| config.column_name(c)).collect();
 		let cfnames: Vec<&str> = cfnames.iter().map(|n| n as &str).collect();
 
-		for _ in 0 .. config.columns.unwrap_or(0) {
-			cf_options.push(col_config(&config, &block_opts)?);
+		for col in 0 .. config.columns.unwrap_or(0) {
+			cf_options.push(col_config(&config, col, &block_opts)?);
+		}
+
+		if compactions_paused {
+			opts.set_disable_auto_compactions(true);
+			for cf_opts in &mut cf_options {
+				cf_opts.set_disable_auto_compactions(true);
+			}
 		}
 
-		let write_opts = WriteOptions::new();
 		let mut read_opts = ReadOptions::new();
 		read_opts.set_verify_checksums(false);
 
-		let mut cfs: Vec<Column> = Vec::new();
-		let db = match config.columns {
-			Some(_) => {
-				match DB::open_cf(&opts, path, &cfnames, &cf_options) {
-					Ok(db) => {
-						cfs = cfnames.iter().map(|n| db.cf_handle(n)
-							.expect("rocksdb opens a cf_handle for each cfname; qed")).collect();
-						Ok(db)
-					}
-					Err(_) => {
-						// retry and create CFs
-						match DB::open_cf(&opts, path, &[], &[]) {
-							Ok(mut db) => {
-								cfs = cfnames.iter()
-									.enumerate()
-									.map(|(i, n)| db.create_cf(n, &cf_options[i]))
-									.collect::<::std::result::Result<_, _>>()
-									.map_err(other_io_err)?;
-								Ok(db)
-							},
-							err => err,
-						}
+		if config.open_mode == OpenMode::ReadWrite && !config.column_names.is_empty() {
+			Self::migrate_numbered_columns(&opts, path, config, &block_opts)?;
+		}
+
+		let opts = Arc::new(opts);
+
+		let db = match config.open_mode {
+			// the columns of read-only databases are never created.
+			OpenMode::ReadOnly => DBAndColumns::open_for_read_only(&opts, path, &cfnames, &cf_options),
+			OpenMode::ReadWrite => match DBAndColumns::open(&opts, path, &cfnames, &cf_options) {
+				Ok(db) => Ok(db),
+				// retry and create CFs
+				Err(_) => DBAndColumns::open(&opts, path, &[], &[]).and_then(|mut db| {
+					for (name, cf_opts) in cfnames.iter().zip(&cf_options) {
+						let cf = db.db.create_cf(name, cf_opts)?;
+						db.cfs.push(cf);
 					}
-				}
+					Ok(db)
+				}),
 			},
-			None => DB::open(&opts, path)
 		};
 
 		let db = match db {
 			Ok(db) => db,
-			Err(ref s) if is_corrupted(s) => {
+			Err(ref s) if is_corrupted(s) && config.open_mode == OpenMode::ReadWrite => {
 				warn!("DB corrupted: {}, attempting repair", s);
-				DB::repair(&opts, path).map_err(other_io_err)?;
-
-				match cfnames.is_empty() {
-					true => DB::open(&opts, path).map_err(other_io_err)?,
-					false => {
-						let db = DB::open_cf(&opts, path, &cfnames, &cf_options).map_err(other_io_err)?;
-						cfs = cfnames.iter().map(|n| db.cf_handle(n)
-							.expect("rocksdb opens a cf_handle for each cfname; qed")).collect();
-						db
-					},
-				}
+				DB::repair(&opts, path).map_err(rocksdb_err)?;
+				DBAndColumns::open(&opts, path, &cfnames, &cf_options).map_err(rocksdb_err)?
 			},
 			Err(s) => {
-				return Err(other_io_err(s))
+				return Err(rocksdb_err(s))
 			}
 		};
-		let num_cols = cfs.len();
+		let num_cols = db.cfs.len();
 		Ok(Database {
-			db: RwLock::new(Some(DBAndColumns{ db: db, cfs: cfs })),
+			db: RwLock::new(Some(Arc::new(db))),
 			config: config.clone(),
-			write_opts: write_opts,
-			overlay: RwLock::new((0..(num_cols + 1)).map(|_| HashMap::new()).collect()),
-			flushing: RwLock::new((0..(num_cols + 1)).map(|_| HashMap::new()).collect()),
+			overlay: RwLock::new((0..(num_cols + 1)).map(|_| ColumnOverlay::default()).collect()),
+			overlay_durability: Mutex::new(None),
+			flushing: RwLock::new((0..(num_cols + 1)).map(|_| ColumnOverlay::default()).collect()),
 			flushing_lock: Mutex::new(false),
 			path: path.to_owned(),
 			read_opts: read_opts,
 			block_opts: block_opts,
+			sequence: AtomicU64::new(0),
+			flushed_sequence: AtomicU64::new(0),
+			compactions_paused: AtomicBool::new(compactions_paused),
+			discarded_writes: Mutex::new(None),
+			stats: stats::RunningDbStats::new(),
 		})
 	}
 
+	// Move the data of the named columns out of the columns named by index, `col<index>`.
+	//
+	// The column being migrated is recorded in the migration file first, so that an interrupted
+	// migration is resumed from that column, copying it again.
+	fn migrate_numbered_columns(
+		opts: &Options,
+		path: &str,
+		config: &DatabaseConfig,
+		block_opts: &BlockBasedOptions,
+	) -> Result<()> {
+		let to_migrate: Vec<u32> = (0..config.columns.unwrap_or(0))
+			.filter(|&col| config.column_name(col) != numbered_column_name(col))
+			.collect();
+		let first = match to_migrate.first() {
+			Some(&col) => col,
+			None => return Ok(()),
+		};
+
+		// open the database with its column families by name.
+		let open = |col: u32, old: bool, new: bool| -> Result<(DB, HashMap<String, Column>)> {
+			let cfs = migration_cfnames(config, col, old, new);
+			let cfnames: Vec<&str> = cfs.iter().map(|&(ref n, _)| n as &str).collect();
+			let cf_options = cfs.iter()
+				.map(|&(_, c)| col_config(config, c, block_opts))
+				.collect::<Result<Vec<_>>>()?;
+			let (db, handles) = DB::open(opts, path, &cfnames, &cf_options).map_err(rocksdb_err)?;
+			Ok((db, cfnames.iter().map(|n| n.to_string()).zip(handles).collect()))
+		};
+
+		let migration_file = Path::new(path).join(Database::MIGRATION_FILE_NAME);
+		// whether the old and the new column exist, for the first column to migrate.
+		let (start, (db, mut handles), mut old_exists, mut new_exists) = if migration_file.exists() {
+			let content = fs::read_to_string(&migration_file)?;
+			let col = content.trim().parse::<u32>()
+				.map_err(|_| Error::Backend(format!("Invalid column migration file: {:?}", content)))?;
+			warn!("Resuming the interrupted migration of column {}", col);
+			[(true, true), (true, false), (false, true)].iter()
+				.filter_map(|&(old, new)| open(col, old, new).ok().map(|db| (col, db, old, new)))
+				.next()
+				.ok_or_else(|| Error::Backend(format!("Failed to resume the migration of column {}", col)))?
+		} else {
+			match open(first, true, false) {
+				Ok(db) => (first, db, true, false),
+				// the database doesn't exist yet, or its columns are already named.
+				Err(_) => return Ok(()),
+			}
+		};
+
+		let write_opts = WriteOptions::new();
+		for col in to_migrate.into_iter().filter(|&col| col >= start) {
+			if col != start {
+				old_exists = true;
+				new_exists = false;
+			}
+			// the old column is only dropped once its data is copied.
+			if !old_exists {
+				continue;
+			}
+			{
+				let mut file = fs::File::create(&migration_file)?;
+				file.write_all(col.to_string().as_bytes())?;
+				file.sync_all()?;
+			}
+			let (old, name) = (numbered_column_name(col), config.column_name(col));
+			if let Some(cf) = handles.remove(&name).filter(|_| new_exists) {
+				db.drop_cf(&cf).map_err(rocksdb_err)?;
+			}
+			info!("Migrating column {} to {}", old, name);
+			let from = handles.remove(&old).expect("rocksdb opens a cf_handle for each cfname; qed");
+			let to = db.create_cf(&name, &col_config(config, col, block_opts)?).map_err(rocksdb_err)?;
+			let mut batch = WriteBatch::new();
+			let mut batch_size = 0;
+			for (key, value) in db.iterator(&from, IteratorMode::Start, &ReadOptions::new()) {
+				batch.put(&to, &key, &value);
+				batch_size += key.len() + value.len();
+				if batch_size >= MIGRATION_BATCH_SIZE {
+					db.write(&mem::replace(&mut batch, WriteBatch::new()), &write_opts).map_err(rocksdb_err)?;
+					batch_size = 0;
+				}
+			}
+			db.write(&batch, &write_opts).map_err(rocksdb_err)?;
+			db.drop_cf(&from).map_err(rocksdb_err)?;
+		}
+		drop((db, handles));
+		fs::remove_file(&migration_file)?;
+		Ok(())
+	}
+
+	/// Get the handle of the column with the given name.
+	pub fn column_id(&self, name: &str) -> Option<ColumnId> {
+		(0..self.num_columns())
+			.find(|&col| self.config.column_name(col) == name)
+			.map(ColumnId::new)
+	}
+
+	fn check_writable(&self) -> Result<()> {
+		match self.config.open_mode {
+			OpenMode::ReadWrite => Ok(()),
+			OpenMode::ReadOnly => Err(Error::ReadOnly),
+		}
+	}
+
 	/// Helper to create new transaction for this database.
 	pub fn transaction(&self) -> DBTransaction {
 		DBTransaction::new()
@@ -391,232 +970,721 @@ impl Database {
 	}
 
 	/// Commit transaction to database.
-	pub fn write_buffered(&self, tr: DBTransaction) {
+	///
+	/// Returns the sequence number of the transaction, which is durably written once
+	/// `last_flushed_sequence` reaches it. Sequence numbers start at 1 and increase by 1 with
+	/// each transaction. `flush` writes all the buffered transactions, it can't stop at one.
+	///
+	/// If the database is opened with `OpenMode::ReadOnly` the transaction is discarded,
+	/// and the next `flush` fails with `Error::ReadOnly`. So is a transaction merging into
+	/// a column without a merge operator, and the next `flush` fails with `Error::Unsupported`.
+	pub fn write_buffered(&self, tr: DBTransaction) -> u64 {
+		if let Err(err) = self.check_writable().and_then(|_| self.check_merges(&tr.ops)) {
+			warn!("Discarding a transaction written to the database: {}", err);
+			*self.discarded_writes.lock() = Some(err);
+			return self.sequence.load(Ordering::SeqCst);
+		}
+		self.stats.tally_transaction();
 		let mut overlay = self.overlay.write();
+		// assigned under the overlay lock, so that the sequence matches the order of the changes.
+		let sequence = self.sequence.fetch_add(1, Ordering::SeqCst) + 1;
+		{
+			let durability = tr.durability.unwrap_or(self.config.durability);
+			let mut overlay_durability = self.overlay_durability.lock();
+			*overlay_durability = cmp::max(*overlay_durability, Some(durability));
+		}
 		let ops = tr.ops;
 		for op in ops {
 			match op {
 				DBOp::Insert { col, key, value } => {
 					let c = Self::to_overlay_column(col);
-					overlay[c].insert(key, KeyState::Insert(value));
+					overlay[c].keys.insert(key, KeyState::Insert(value));
 				},
 				DBOp::Delete { col, key } => {
 					let c = Self::to_overlay_column(col);
-					overlay[c].insert(key, KeyState::Delete);
+					overlay[c].keys.insert(key, KeyState::Delete);
+				},
+				DBOp::DeletePrefix { col, prefix } => {
+					let c = Self::to_overlay_column(col);
+					overlay[c].delete_prefix(prefix);
+				},
+				DBOp::Merge { col, key, operand } => {
+					let c = Self::to_overlay_column(col);
+					let merge = self.config.merge_operators.get(&col).expect("merges are checked above; qed");
+					overlay[c].merge(key, vec![operand], merge);
 				},
 			}
 		};
+		sequence
+	}
+
+	// Check that the merges of a transaction go to columns with a merge operator.
+	fn check_merges(&self, ops: &[DBOp]) -> Result<()> {
+		for op in ops {
+			if let DBOp::Merge { col, .. } = *op {
+				if !self.config.merge_operators.contains_key(&col) {
+					return Err(Error::Unsupported("No merge operator registered for the column"));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	// Add deletion of all keys starting with `prefix` to the batch.
+	fn batch_delete_prefix(db: &DB, batch: &mut WriteBatch, cf: &Column, prefix: &[u8]) {
+		match kvdb::end_prefix(prefix) {
+			Some(end) => batch.delete_range(cf, prefix, &end),
+			// no key is past all the keys with the prefix, so they are deleted one by one.
+			None => {
+				let iter = db.iterator(cf, IteratorMode::From(prefix, Direction::Forward), &ReadOptions::new());
+				for (key, _) in iter {
+					batch.delete(cf, &key);
+				}
+			},
+		}
 	}
 
 	/// Commit buffered changes to database. Must be called under `flush_lock`
-	fn write_flushing_with_lock(&self, _lock: &mut MutexGuard<bool>) -> io::Result<()> {
+	fn write_flushing_with_lock(&self, _lock: &mut MutexGuard<bool>) -> Result<()> {
 		match *self.db.read() {
-			Some(DBAndColumns { ref db, ref cfs }) => {
-				let batch = WriteBatch::new();
-				mem::swap(&mut *self.overlay.write(), &mut *self.flushing.write());
+			Some(ref db_and_cols) => {
+				let db = &db_and_cols.db;
+				let mut batch = WriteBatch::new();
+				// the durability is `None` if nothing was written since the last flush.
+				let (sequence, durability) = {
+					let mut overlay = self.overlay.write();
+					mem::swap(&mut *overlay, &mut *self.flushing.write());
+					(self.sequence.load(Ordering::SeqCst), self.overlay_durability.lock().take())
+				};
+				// readers resolving buffered merges must not see them both in `flushing` and in RocksDB,
+				// so the write below is done under an exclusive lock.
+				let flushing = self.flushing.upgradable_read();
 				{
-					for (c, column) in self.flushing.read().iter().enumerate() {
-						for (key, state) in column.iter() {
+					for (c, column) in flushing.iter().enumerate() {
+						let col = if c > 0 { Some(c as u32 - 1) } else { None };
+						let cf = db_and_cols.cf(col)?;
+						for prefix in column.deleted_prefixes.iter() {
+							self.stats.tally_write(prefix.len());
+							Self::batch_delete_prefix(db, &mut batch, cf, prefix);
+						}
+						for (key, state) in column.keys.iter() {
 							match *state {
+								KeyState::Insert(ref value) => {
+									self.stats.tally_write(key.len() + value.len());
+									batch.put(cf, key, value);
+								},
 								KeyState::Delete => {
-									if c > 0 {
-										batch.delete_cf(cfs[c - 1], key).map_err(other_io_err)?;
-									} else {
-										batch.delete(key).map_err(other_io_err)?;
-									}
+									self.stats.tally_write(key.len());
+									batch.delete(cf, key);
 								},
-								KeyState::Insert(ref value) => {
-									if c > 0 {
-										batch.put_cf(cfs[c - 1], key, value).map_err(other_io_err)?;
-									} else {
-										batch.put(key, value).map_err(other_io_err)?;
-									}
+								KeyState::Merge(ref operands) => for operand in operands {
+									self.stats.tally_write(key.len() + operand.len());
+									batch.merge(cf, key, operand);
 								},
 							}
 						}
 					}
 				}
 
+				let mut flushing = RwLockUpgradableReadGuard::upgrade(flushing);
 				check_for_corruption(
 					&self.path,
-					db.write_opt(batch, &self.write_opts))?;
+					db.write(&batch, &write_options(durability.unwrap_or(self.config.durability))))?;
 
-				for column in self.flushing.write().iter_mut() {
-					column.clear();
-					column.shrink_to_fit();
+				for column in flushing.iter_mut() {
+					column.keys.clear();
+					column.keys.shrink_to_fit();
+					column.deleted_prefixes.clear();
+				}
+				// changes written without the WAL are not durable.
+				if durability.map_or(false, |durability| durability != Durability::NoWal) {
+					self.flushed_sequence.store(sequence, Ordering::SeqCst);
 				}
 				Ok(())
 			},
-			None => Err(other_io_err("Database is closed"))
+			None => Err(Error::Closed)
 		}
 	}
 
 	/// Commit buffered changes to database.
-	pub fn flush(&self) -> io::Result<()> {
+	///
+	/// Fails if `write_buffered` discarded transactions since the last flush,
+	/// after committing the others.
+	pub fn flush(&self) -> Result<()> {
+		let discarded = self.discarded_writes.lock().take();
+		// nothing is ever buffered in a read-only database.
+		if self.check_writable().is_err() {
+			return discarded.map_or(Ok(()), Err);
+		}
 		let mut lock = self.flushing_lock.lock();
 		// If RocksDB batch allocation fails the thread gets terminated and the lock is released.
 		// The value inside the lock is used to detect that.
 		if *lock {
 			// This can only happen if another flushing thread is terminated unexpectedly.
-			return Err(other_io_err("Database write failure. Running low on memory perhaps?"))
+			return Err(Error::Backend("Database write failure. Running low on memory perhaps?".into()))
 		}
 		*lock = true;
 		let result = self.write_flushing_with_lock(&mut lock);
 		*lock = false;
-		result
+		result.and_then(|_| discarded.map_or(Ok(()), Err))
+	}
+
+	/// Sequence number of the last transaction added with `write_buffered`
+	/// which has been durably written with the write-ahead log, `0` if there is none.
+	///
+	/// Flushes of transactions which all have `Durability::NoWal` don't advance it.
+	/// Those transactions are only durable once RocksDB has flushed its memtables.
+	pub fn last_flushed_sequence(&self) -> u64 {
+		self.flushed_sequence.load(Ordering::SeqCst)
 	}
 
 	/// Commit transaction to database.
-	pub fn write(&self, tr: DBTransaction) -> io::Result<()> {
+	pub fn write(&self, tr: DBTransaction) -> Result<()> {
+		self.check_writable()?;
+		self.check_merges(&tr.ops)?;
 		match *self.db.read() {
-			Some(DBAndColumns { ref db, ref cfs }) => {
-				let batch = WriteBatch::new();
+			Some(ref db_and_cols) => {
+				self.stats.tally_transaction();
+				let mut batch = WriteBatch::new();
+				// keys written to the batch, which prefix deletions iterating RocksDB can't find yet.
+				let mut batched = HashSet::new();
 				let ops = tr.ops;
 				for op in ops {
-					// remove any buffered operation for this key
-					self.overlay.write()[Self::to_overlay_column(op.col())].remove(op.key());
+					let cf = db_and_cols.cf(op.col())?;
+					self.stats.tally_write(op.key().len() + match op {
+						DBOp::Insert { ref value, .. } => value.len(),
+						DBOp::Merge { ref operand, .. } => operand.len(),
+						_ => 0,
+					});
+					{
+						let overlay = &mut self.overlay.write()[Self::to_overlay_column(op.col())];
+						match op {
+							// a merge into a buffered value has to be applied after it.
+							DBOp::Merge { col, ref key, ref operand } if overlay.get(key).is_some() => {
+								let merge = self.config.merge_operators.get(&col).expect("merges are checked above; qed");
+								overlay.merge(key.clone(), vec![operand.clone()], merge);
+								continue;
+							},
+							// a buffered prefix deletion will be flushed after this write,
+							// so the insertion needs to be buffered as well to survive it.
+							DBOp::Insert { ref key, ref value, .. } if overlay.is_deleted_by_prefix(key) => {
+								overlay.keys.insert(key.clone(), KeyState::Insert(value.clone()));
+							},
+							DBOp::DeletePrefix { ref prefix, .. } => {
+								overlay.keys.retain(|k, _| !k.starts_with(prefix));
+							},
+							// remove any buffered operation for this key
+							_ => {
+								overlay.keys.remove(op.key());
+							},
+						}
+					}
 
 					match op {
-						DBOp::Insert { col, key, value } => match col {
-							None => batch.put(&key, &value).map_err(other_io_err)?,
-							Some(c) => batch.put_cf(cfs[c as usize], &key, &value).map_err(other_io_err)?,
+						DBOp::Insert { col, key, value } => {
+							batch.put(cf, &key, &value);
+							batched.insert((col, key));
+						},
+						DBOp::Delete { key, .. } => batch.delete(cf, &key),
+						DBOp::DeletePrefix { col, prefix } => {
+							Self::batch_delete_prefix(&db_and_cols.db, &mut batch, cf, &prefix);
+							for (_, key) in batched.iter().filter(|(c, key)| *c == col && key.starts_with(&prefix)) {
+								batch.delete(cf, key);
+							}
+						},
+						DBOp::Merge { col, key, operand } => {
+							batch.merge(cf, &key, &operand);
+							batched.insert((col, key));
 						},
-						DBOp::Delete { col, key } => match col {
-							None => batch.delete(&key).map_err(other_io_err)?,
-							Some(c) => batch.delete_cf(cfs[c as usize], &key).map_err(other_io_err)?,
-						}
 					}
 				}
 
-				check_for_corruption(&self.path, db.write_opt(batch, &self.write_opts))
+				let durability = tr.durability.unwrap_or(self.config.durability);
+				check_for_corruption(&self.path, db_and_cols.db.write(&batch, &write_options(durability)))
 			},
-			None => Err(other_io_err("Database is closed")),
+			None => Err(Error::Closed),
 		}
 	}
 
 	/// Get value by key.
-	pub fn get(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<DBValue>> {
+	pub fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>> {
+		match *self.db.read() {
+			Some(ref db) => {
+				// make sure the column exists before looking into the overlay.
+				db.cf(col)?;
+				let c = Self::to_overlay_column(col);
+				let buffered = match self.overlay.read()[c].get(key) {
+					Some(state) => Some(state.clone()),
+					None => self.flushing.read()[c].get(key).cloned(),
+				};
+				let (value, cached) = match buffered {
+					Some(KeyState::Insert(value)) => (Some(value), true),
+					Some(KeyState::Delete) => (None, true),
+					Some(KeyState::Merge(_)) => self.read_merged(db, col, key)?,
+					None => (db.get_opt(col, key, &self.read_opts)?, false),
+				};
+				self.stats.tally_read(value.as_ref().map_or(0, |v| v.len()), cached);
+				Ok(value)
+			},
+			None => Err(Error::Closed),
+		}
+	}
+
+	/// Get values for multiple keys at once, in the order of `keys`.
+	///
+	/// Buffered values are looked up under a single lock, the rest are read from RocksDB afterwards.
+	pub fn get_many(&self, col: Option<u32>, keys: &[&[u8]]) -> Result<Vec<Option<DBValue>>> {
 		match *self.db.read() {
-			Some(DBAndColumns { ref db, ref cfs }) => {
-				let overlay = &self.overlay.read()[Self::to_overlay_column(col)];
-				match overlay.get(key) {
-					Some(&KeyState::Insert(ref value)) => Ok(Some(value.clone())),
-					Some(&KeyState::Delete) => Ok(None),
-					None => {
-						let flushing = &self.flushing.read()[Self::to_overlay_column(col)];
-						match flushing.get(key) {
-							Some(&KeyState::Insert(ref value)) => Ok(Some(value.clone())),
-							Some(&KeyState::Delete) => Ok(None),
+			Some(ref db_and_cols) => {
+				// make sure the column exists before looking into the overlay.
+				db_and_cols.cf(col)?;
+				let c = Self::to_overlay_column(col);
+				let mut values = Vec::with_capacity(keys.len());
+				// positions of the keys which are not buffered.
+				let mut missing = Vec::new();
+				// positions of the keys with buffered merge operands.
+				let mut merged = Vec::new();
+				{
+					let overlay = &self.overlay.read()[c];
+					let flushing = &self.flushing.read()[c];
+					for (i, key) in keys.iter().enumerate() {
+						match overlay.get(key).or_else(|| flushing.get(key)) {
+							Some(&KeyState::Insert(ref value)) => {
+								self.stats.tally_read(value.len(), true);
+								values.push(Some(value.clone()));
+							},
+							Some(&KeyState::Delete) => {
+								self.stats.tally_read(0, true);
+								values.push(None);
+							},
+							Some(&KeyState::Merge(_)) => {
+								values.push(None);
+								merged.push(i);
+							},
 							None => {
-								col.map_or_else(
-									|| db.get_opt(key, &self.read_opts).map(|r| r.map(|v| DBValue::from_slice(&v))),
-									|c| db.get_cf_opt(cfs[c as usize], key, &self.read_opts).map(|r| r.map(|v| DBValue::from_slice(&v))))
-									.map_err(other_io_err)
+								values.push(None);
+								missing.push(i);
 							},
 						}
-					},
+					}
+				}
+
+				for i in merged {
+					let (value, cached) = self.read_merged(db_and_cols, col, keys[i])?;
+					self.stats.tally_read(value.as_ref().map_or(0, |v| v.len()), cached);
+					values[i] = value;
+				}
+
+				let missing_keys: Vec<_> = missing.iter().map(|&i| keys[i]).collect();
+				let found = db_and_cols.db.multi_get(db_and_cols.cf(col)?, &missing_keys, &self.read_opts).map_err(rocksdb_err)?;
+				for (i, value) in missing.into_iter().zip(found) {
+					self.stats.tally_read(value.as_ref().map_or(0, |v| v.len()), false);
+					values[i] = value.map(DBValue::from_vec);
 				}
+				Ok(values)
 			},
-			None => Ok(None),
+			None => Err(Error::Closed),
 		}
 	}
 
 	/// Get value by partial key. Prefix size should match configured prefix size. Only searches flushed values.
 	// TODO: support prefix seek for unflushed data
-	pub fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>> {
-		self.iter_from_prefix(col, prefix).and_then(|mut iter| {
-			match iter.next() {
-				// TODO: use prefix_same_as_start read option (not available in C API currently)
-				Some((k, v)) => if k[0 .. prefix.len()] == prefix[..] { Some(v) } else { None },
-				_ => None
-			}
-		})
+	pub fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Result<Option<Box<[u8]>>> {
+		let mut iter = self.iter_from_prefix(col, prefix)?;
+		match iter.next() {
+			// TODO: use prefix_same_as_start read option (not available in C API currently)
+			Some((k, v)) => Ok(if k[0 .. prefix.len()] == prefix[..] { Some(v) } else { None }),
+			_ => Ok(None)
+		}
 	}
 
 	/// Get database iterator for flushed data.
-	pub fn iter(&self, col: Option<u32>) -> Option<DatabaseIterator> {
+	pub fn iter(&self, col: Option<u32>) -> Result<DatabaseIterator> {
 		match *self.db.read() {
-			Some(DBAndColumns { ref db, ref cfs }) => {
-				let overlay = &self.overlay.read()[Self::to_overlay_column(col)];
-				let mut overlay_data = overlay.iter()
-					.filter_map(|(k, v)| match *v {
-						KeyState::Insert(ref value) =>
-							Some((k.clone().into_vec().into_boxed_slice(), value.clone().into_vec().into_boxed_slice())),
-						KeyState::Delete => None,
-					}).collect::<Vec<_>>();
-				overlay_data.sort();
-
-				let iter = col.map_or_else(
-					|| db.iterator_opt(IteratorMode::Start, &self.read_opts),
-					|c| db.iterator_cf_opt(cfs[c as usize], IteratorMode::Start, &self.read_opts)
-						.expect("iterator params are valid; qed")
-				);
-
-				Some(DatabaseIterator {
-					iter: interleave_ordered(overlay_data, iter),
+			Some(ref db) => {
+				let iter = db.iterator_opt(col, IteratorMode::Start, &self.read_opts)?;
+				let (buffered, overlay_data) = self.overlay_data(db, col, |_| true)?;
+
+				// the buffered state of a key, deleted or not, replaces the one in RocksDB.
+				let iter = iter.filter(move |(k, _)| buffered.get(k).is_none());
+
+				Ok(DatabaseIterator {
+					iter: Box::new(interleave_ordered(overlay_data, iter)),
 					_marker: PhantomData,
 				})
 			},
-			None => None,
+			None => Err(Error::Closed),
+		}
+	}
+
+	// Resolve the buffered merge operands of a key. The buffered changes stay locked while
+	// reading RocksDB, so that operands are not applied twice if a flush completes meanwhile.
+	fn read_merged(&self, db: &DBAndColumns, col: Option<u32>, key: &[u8]) -> Result<(Option<DBValue>, bool)> {
+		let c = Self::to_overlay_column(col);
+		let overlay = self.overlay.read();
+		let flushing = self.flushing.read();
+		read_through(self.config.merge_operators.get(&col), db, col, key, &[&overlay[c], &flushing[c]], &self.read_opts)
+	}
+
+	// The buffered changes of a column, including the ones being flushed, and the buffered values
+	// of the keys passing `filter`, sorted by key.
+	fn overlay_data<F: Fn(&[u8]) -> bool>(&self, db: &DBAndColumns, col: Option<u32>, filter: F)
+		-> Result<(ColumnOverlay, Vec<KeyValuePair>)>
+	{
+		let c = Self::to_overlay_column(col);
+		let merge = self.config.merge_operators.get(&col);
+		// merges are resolved under the locks, so that a flush doesn't write their operands meanwhile.
+		let overlay = self.overlay.read();
+		let flushing = self.flushing.read();
+		let mut column = flushing[c].clone();
+		column.extend(&overlay[c], merge);
+		let mut data = Vec::new();
+		for (k, v) in column.keys.iter().filter(|&(k, _)| filter(k)) {
+			let value = match *v {
+				KeyState::Insert(ref value) => Some(value.clone()),
+				KeyState::Delete => None,
+				KeyState::Merge(_) => read_through(merge, db, col, k, &[&column], &self.read_opts)?.0,
+			};
+			if let Some(value) = value {
+				data.push((k.clone().into_vec().into_boxed_slice(), value.into_vec().into_boxed_slice()));
+			}
 		}
+		data.sort();
+		Ok((column, data))
+	}
+
+	// Prefixes deleted by changes which have not been written to the database yet.
+	fn deleted_prefixes(&self, col: Option<u32>) -> Vec<ElasticArray32<u8>> {
+		let c = Self::to_overlay_column(col);
+		let mut prefixes = self.overlay.read()[c].deleted_prefixes.clone();
+		prefixes.extend(self.flushing.read()[c].deleted_prefixes.iter().cloned());
+		prefixes
 	}
 
-	fn iter_from_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<DatabaseIterator> {
+	fn iter_from_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Result<DatabaseIterator> {
 		match *self.db.read() {
-			Some(DBAndColumns { ref db, ref cfs }) => {
-				let iter = col.map_or_else(|| db.iterator_opt(IteratorMode::From(prefix, Direction::Forward), &self.read_opts),
-					|c| db.iterator_cf_opt(cfs[c as usize], IteratorMode::From(prefix, Direction::Forward), &self.read_opts)
-						.expect("iterator params are valid; qed"));
+			Some(ref db) => {
+				let iter = db.iterator_opt(col, IteratorMode::From(prefix, Direction::Forward), &self.read_opts)?;
+				let deleted_prefixes = self.deleted_prefixes(col);
+				let iter = iter.filter(move |(k, _)| !deleted_prefixes.iter().any(|prefix| k.starts_with(prefix)));
 
-				Some(DatabaseIterator {
-					iter: interleave_ordered(Vec::new(), iter),
+				Ok(DatabaseIterator {
+					iter: Box::new(interleave_ordered(Vec::new(), iter)),
 					_marker: PhantomData,
 				})
 			},
-			None => None,
+			None => Err(Error::Closed),
 		}
 	}
 
-	/// Close the database
-	fn close(&self) {
-		*self.db.write() = None;
-		self.overlay.write().clear();
-		self.flushing.write().clear();
-	}
+	/// Take a consistent read-only view of the database, including buffered changes.
+	pub fn snapshot(&self) -> Result<DatabaseSnapshot> {
+		// make sure no flush is in progress, otherwise we could observe a half-written batch.
+		let _lock = self.flushing_lock.lock();
+		let db = match *self.db.read() {
+			Some(ref db) => db.clone(),
+			None => return Err(Error::Closed),
+		};
+		let mut read_opts = ReadOptions::new();
+		read_opts.set_verify_checksums(false);
+		let snapshot = db.db.snapshot(read_opts);
+
+		let overlay = self.flushing.read().iter()
+			.zip(self.overlay.read().iter())
+			.enumerate()
+			.map(|(c, (flushing, overlay))| {
+				let col = c.checked_sub(1).map(|c| c as u32);
+				let mut column = flushing.clone();
+				column.extend(overlay, self.config.merge_operators.get(&col));
+				column
+			})
+			.collect();
 
-	/// Restore the database from a copy at given path.
-	pub fn restore(&self, new_db: &str) -> io::Result<()> {
-		self.close();
+		Ok(DatabaseSnapshot {
+			overlay,
+			snapshot,
+			db,
+			merge_operators: &self.config.merge_operators,
+		})
+	}
 
-		// swap is guaranteed to be atomic
-		match swap(new_db, &self.path) {
-			Ok(_) => {
-				// ignore errors
-				let _ = fs::remove_dir_all(new_db);
+	/// Get database iterator over the keys between `start` and `end`, in the given direction.
+	/// Includes buffered insertions.
+	pub fn iter_range<'a>(
+		&'a self,
+		col: Option<u32>,
+		start: Bound<&'a [u8]>,
+		end: Bound<&'a [u8]>,
+		direction: kvdb::Direction,
+	) -> Result<DatabaseIterator<'a>> {
+		match *self.db.read() {
+			Some(ref db) => {
+				// seek to the bound iteration starts from, an excluded bound is skipped while iterating.
+				let mode = match (direction, start, end) {
+					(kvdb::Direction::Forward, Bound::Included(key), _)
+						| (kvdb::Direction::Forward, Bound::Excluded(key), _) => IteratorMode::From(key, Direction::Forward),
+					(kvdb::Direction::Forward, Bound::Unbounded, _) => IteratorMode::Start,
+					(kvdb::Direction::Reverse, _, Bound::Included(key))
+						| (kvdb::Direction::Reverse, _, Bound::Excluded(key)) => IteratorMode::From(key, Direction::Reverse),
+					(kvdb::Direction::Reverse, _, Bound::Unbounded) => IteratorMode::End,
+				};
+				let iter = db.iterator_opt(col, mode, &self.read_opts)?;
+				let (buffered, overlay_data) =
+					self.overlay_data(db, col, |k| kvdb::is_after_start(k, start) && kvdb::is_before_end(k, end))?;
+
+				// the buffered state of a key, deleted or not, replaces the one in RocksDB.
+				let iter = iter.filter(move |(k, _)| buffered.get(k).is_none());
+
+				let iter: Box<dyn Iterator<Item=_> + 'a> = match direction {
+					kvdb::Direction::Forward => Box::new(
+						interleave_ordered(overlay_data, iter)
+							.skip_while(move |(k, _)| !kvdb::is_after_start(k, start))
+							.take_while(move |(k, _)| kvdb::is_before_end(k, end))
+					),
+					// merge both sources in descending order by reversing the key ordering.
+					kvdb::Direction::Reverse => Box::new(
+						interleave_ordered(
+							overlay_data.into_iter().rev().map(cmp::Reverse),
+							iter.map(cmp::Reverse),
+						)
+							.map(|cmp::Reverse(kv)| kv)
+							.skip_while(move |(k, _)| !kvdb::is_before_end(k, end))
+							.take_while(move |(k, _)| kvdb::is_after_start(k, start))
+					),
+				};
+
+				Ok(DatabaseIterator {
+					iter,
+					_marker: PhantomData,
+				})
 			},
-			Err(err) => {
-				debug!("DB atomic swap failed: {}", err);
-				match swap_nonatomic(new_db, &self.path) {
-					Ok(_) => {
-						// ignore errors
-						let _ = fs::remove_dir_all(new_db);
-					},
+			None => Err(Error::Closed),
+		}
+	}
+
+	/// Get the I/O statistics of the database since it was opened.
+	pub fn io_stats(&self) -> IoStats {
+		let mut stats = self.stats.snapshot();
+		if let Some(ref db) = *self.db.read() {
+			stats.block_cache_usage = self.block_cache_usage(db);
+			stats.delayed_write_rate = db.property(db.db.default_cf(), "rocksdb.actual-delayed-write-rate");
+			if let Some(statistics) = db.opts.statistics() {
+				stats.block_cache_hits = ticker(&statistics, "rocksdb.block.cache.hit");
+				stats.block_cache_misses = ticker(&statistics, "rocksdb.block.cache.miss");
+				stats.stall_time = Duration::from_micros(ticker(&statistics, "rocksdb.stall.micros"));
+			}
+		}
+		stats
+	}
+
+	// Memory used by the block caches of all the columns, counting the shared cache once.
+	fn block_cache_usage(&self, db: &DBAndColumns) -> u64 {
+		let usage = |cf: &Column| db.property(cf, "rocksdb.block-cache-usage");
+		let columns = iter::once((None, db.db.default_cf()))
+			.chain(db.cfs.iter().enumerate().map(|(col, cf)| (Some(col as u32), cf)));
+		let (shared, own): (Vec<_>, Vec<_>) = columns.partition(|&(col, _)| self.config.uses_shared_cache(col));
+		own.into_iter().map(|(_, cf)| usage(cf)).sum::<u64>() + shared.first().map_or(0, |&(_, cf)| usage(cf))
+	}
+
+	/// Get estimated size statistics of a column from RocksDB properties.
+	/// Changes buffered with `write_buffered` are not accounted for.
+	pub fn column_stats(&self, col: Option<u32>) -> Result<ColumnStats> {
+		match *self.db.read() {
+			Some(ref db) => {
+				let cf = db.cf(col)?;
+				let mut num_sst_files = 0;
+				for level in 0..NUM_LEVELS {
+					num_sst_files += db.property(cf, &format!("rocksdb.num-files-at-level{}", level));
+				}
+				Ok(ColumnStats {
+					num_keys: db.property(cf, "rocksdb.estimate-num-keys"),
+					live_data_size: db.property(cf, "rocksdb.estimate-live-data-size"),
+					num_sst_files,
+					memtable_size: db.property(cf, "rocksdb.cur-size-all-mem-tables"),
+				})
+			},
+			None => Err(Error::Closed),
+		}
+	}
+
+	/// Close the database
+	fn close(&self) -> Result<()> {
+		*self.db.write() = None;
+		self.overlay.write().clear();
+		self.flushing.write().clear();
+		Ok(())
+	}
+
+	/// Restore the database from a copy at given path.
+	///
+	/// Fails while snapshots are alive: they keep the old files open, so the restored
+	/// database couldn't be opened at the same path.
+	pub fn restore(&self, new_db: &str) -> Result<()> {
+		self.check_writable()?;
+		if self.db.read().as_ref().map_or(false, |db| db.has_snapshots()) {
+			return Err(Error::Backend("The database cannot be restored while snapshots are alive".into()));
+		}
+		self.close()?;
+
+		// swap is guaranteed to be atomic
+		match swap(new_db, &self.path) {
+			Ok(_) => {
+				// ignore errors
+				let _ = fs::remove_dir_all(new_db);
+			},
+			Err(err) => {
+				debug!("DB atomic swap failed: {}", err);
+				match swap_nonatomic(new_db, &self.path) {
+					Ok(_) => {
+						// ignore errors
+						let _ = fs::remove_dir_all(new_db);
+					},
 					Err(err) => {
 						warn!("Failed to swap DB directories: {:?}", err);
-						return Err(io::Error::new(io::ErrorKind::Other, "DB restoration failed: could not swap DB directories"));
+						return Err(Error::Backend("DB restoration failed: could not swap DB directories".into()));
 					}
 				}
 			}
 		}
 
 		// reopen the database and steal handles into self
-		let db = Self::open(&self.config, &self.path)?;
+		let db = Self::open_inner(&self.config, &self.path, self.compactions_paused())?;
 		*self.db.write() = mem::replace(&mut *db.db.write(), None);
 		*self.overlay.write() = mem::replace(&mut *db.overlay.write(), Vec::new());
 		*self.flushing.write() = mem::replace(&mut *db.flushing.write(), Vec::new());
+		*self.overlay_durability.lock() = None;
+		Ok(())
+	}
+
+	/// Create a checkpoint of the database at the given path, which must not exist yet.
+	///
+	/// Buffered changes are flushed first. SST files are hard-linked if the path is on the same
+	/// filesystem, so this is cheap. The checkpoint can be opened as a database or passed to `restore`.
+	pub fn checkpoint(&self, path: &str) -> Result<()> {
+		self.flush()?;
+		match *self.db.read() {
+			Some(ref db) => db.db.create_checkpoint(path).map_err(rocksdb_err),
+			None => Err(Error::Closed),
+		}
+	}
+
+	/// Create an incremental backup of the database in the given backup directory.
+	/// Buffered changes are flushed first.
+	///
+	/// Returns the id of the new backup, which can be passed to `restore_from_backup`.
+	pub fn backup(&self, backup_dir: &str) -> Result<u32> {
+		self.flush()?;
+		match *self.db.read() {
+			Some(ref db) => {
+				let mut engine = BackupEngine::open(&Options::new(), backup_dir).map_err(rocksdb_err)?;
+				engine.create_new_backup(&db.db).map_err(rocksdb_err)?;
+				engine.backup_ids().into_iter()
+					.max()
+					.ok_or_else(|| Error::Backend("Backup was not created".into()))
+			},
+			None => Err(Error::Closed),
+		}
+	}
+
+	/// Replace the database with the backup with the given id from the given backup directory,
+	/// like `restore`.
+	///
+	/// RocksDB can only restore the latest backup, restoring an older one fails.
+	pub fn restore_from_backup(&self, backup_dir: &str, backup_id: u32) -> Result<()> {
+		let mut engine = BackupEngine::open(&Options::new(), backup_dir).map_err(rocksdb_err)?;
+		if engine.backup_ids().into_iter().max() != Some(backup_id) {
+			return Err(Error::Backend(format!("Backup {} is not the latest backup in {}", backup_id, backup_dir)));
+		}
+
+		let restore_path = format!("{}.restore", self.path);
+		if Path::new(&restore_path).exists() {
+			fs::remove_dir_all(&restore_path)?;
+		}
+		engine.restore_from_latest_backup(&restore_path).map_err(rocksdb_err)?;
+
+		self.restore(&restore_path)
+	}
+
+	/// Start a bulk import of sorted data, see `BulkLoader`.
+	pub fn bulk_loader(&self) -> Result<BulkLoader> {
+		BulkLoader::new(self)
+	}
+
+	/// Compact the keys of a column between `start` and `end`, or from/to the first/last key if not given.
+	///
+	/// Buffered changes are flushed first, so that buffered deletions are compacted as well.
+	/// Blocks until the compaction is done.
+	pub fn compact_range(&self, col: Option<u32>, start: Option<&[u8]>, end: Option<&[u8]>) -> Result<()> {
+		self.check_writable()?;
+		self.flush()?;
+		match *self.db.read() {
+			Some(ref db) => {
+				db.db.compact_range(db.cf(col)?, start, end);
+				Ok(())
+			},
+			None => Err(Error::Closed),
+		}
+	}
+
+	/// Compact a whole column. See `compact_range`.
+	pub fn compact_column(&self, col: Option<u32>) -> Result<()> {
+		self.compact_range(col, None, None)
+	}
+
+	/// Pause automatic background compactions of all the columns, e.g. during a bulk import.
+	/// Changes can still be written, and manual compactions still run.
+	///
+	/// The compactions are disabled with RocksDB's `SetOptions`, whose C API only changes the
+	/// options of the default column. A database with other columns is reopened with the
+	/// compactions disabled instead, which fails while snapshots are alive. Buffered changes are kept.
+	pub fn pause_compactions(&self) -> Result<()> {
+		self.set_compactions_paused(true)
+	}
+
+	/// Resume automatic background compactions paused with `pause_compactions`.
+	/// A database with columns is reopened like in `pause_compactions`.
+	pub fn resume_compactions(&self) -> Result<()> {
+		self.set_compactions_paused(false)
+	}
+
+	// Enable or disable automatic compactions, reopening the database if it has columns.
+	fn set_compactions_paused(&self, paused: bool) -> Result<()> {
+		self.check_writable()?;
+		// no flush may write to the database while it is reopened.
+		let _flushing = self.flushing_lock.lock();
+		let mut db = self.db.write();
+		if self.compactions_paused() == paused {
+			return Ok(());
+		}
+		// reopen the columns added or dropped since the database was opened.
+		let mut config = self.config.clone();
+		match *db {
+			Some(ref db) if db.cfs.is_empty() => {
+				let disabled = if paused { "true" } else { "false" };
+				db.db.set_options(&[("disable_auto_compactions", disabled)]).map_err(rocksdb_err)?;
+				self.compactions_paused.store(paused, Ordering::SeqCst);
+				return Ok(());
+			},
+			Some(ref db) if db.has_snapshots() =>
+				return Err(Error::Backend("The database cannot be reopened while snapshots are alive".into())),
+			Some(ref db) => if config.columns.is_some() || !db.cfs.is_empty() {
+				config.columns = Some(db.cfs.len() as u32);
+			},
+			None => return Err(Error::Closed),
+		}
+		// RocksDB releases the lock of the files when the old database is dropped.
+		*db = None;
+		let reopened = Self::open_inner(&config, &self.path, paused)?;
+		*db = mem::replace(&mut *reopened.db.write(), None);
+		self.compactions_paused.store(paused, Ordering::SeqCst);
 		Ok(())
 	}
 
+	/// Whether automatic compactions are paused.
+	pub fn compactions_paused(&self) -> bool {
+		self.compactions_paused.load(Ordering::SeqCst)
+	}
+
 	/// The number of non-default column families.
 	pub fn num_columns(&self) -> u32 {
 		self.db.read().as_ref()
@@ -626,30 +1694,44 @@ impl Database {
 	}
 
 	/// Drop a column family.
-	pub fn drop_column(&self) -> io::Result<()> {
+	pub fn drop_column(&self) -> Result<()> {
+		self.check_writable()?;
 		match *self.db.write() {
-			Some(DBAndColumns { ref mut db, ref mut cfs }) => {
-				if let Some(col) = cfs.pop() {
-					let name = format!("col{}", cfs.len());
-					drop(col);
-					db.drop_cf(&name).map_err(other_io_err)?;
+			Some(ref mut db) => {
+				// snapshots keep the columns they were taken with.
+				let mut columns = (**db).clone();
+				if let Some(cf) = columns.cfs.pop() {
+					columns.db.drop_cf(&cf).map_err(rocksdb_err)?;
+					*db = Arc::new(columns);
+					self.overlay.write().pop();
+					self.flushing.write().pop();
 				}
 				Ok(())
 			},
-			None => Ok(()),
+			None => Err(Error::Closed),
 		}
 	}
 
 	/// Add a column family.
-	pub fn add_column(&self) -> io::Result<()> {
+	pub fn add_column(&self) -> Result<()> {
+		self.check_writable()?;
 		match *self.db.write() {
-			Some(DBAndColumns { ref mut db, ref mut cfs }) => {
-				let col = cfs.len() as u32;
-				let name = format!("col{}", col);
-				cfs.push(db.create_cf(&name, &col_config(&self.config, &self.block_opts)?).map_err(other_io_err)?);
+			Some(ref mut db) => {
+				let mut columns = (**db).clone();
+				let col = columns.cfs.len() as u32;
+				let name = self.config.column_name(col);
+				let mut opts = col_config(&self.config, col, &self.block_opts)?;
+				if self.compactions_paused() {
+					opts.set_disable_auto_compactions(true);
+				}
+				let cf = columns.db.create_cf(&name, &opts).map_err(rocksdb_err)?;
+				columns.cfs.push(cf);
+				*db = Arc::new(columns);
+				self.overlay.write().push(ColumnOverlay::default());
+				self.flushing.write().push(ColumnOverlay::default());
 				Ok(())
 			},
-			None => Ok(()),
+			None => Err(Error::Closed),
 		}
 	}
 }
@@ -657,43 +1739,80 @@ impl Database {
 // duplicate declaration of methods here to avoid trait import in certain existing cases
 // at time of addition.
 impl KeyValueDB for Database {
-	fn get(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<DBValue>> {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>> {
 		Database::get(self, col, key)
 	}
 
-	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>> {
+	fn get_many(&self, col: Option<u32>, keys: &[&[u8]]) -> Result<Vec<Option<DBValue>>> {
+		Database::get_many(self, col, keys)
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Result<Option<Box<[u8]>>> {
 		Database::get_by_prefix(self, col, prefix)
 	}
 
 	fn write_buffered(&self, transaction: DBTransaction) {
-		Database::write_buffered(self, transaction)
+		Database::write_buffered(self, transaction);
 	}
 
-	fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+	fn write(&self, transaction: DBTransaction) -> Result<()> {
 		Database::write(self, transaction)
 	}
 
-	fn flush(&self) -> io::Result<()> {
+	fn flush(&self) -> Result<()> {
 		Database::flush(self)
 	}
 
-	fn iter<'a>(&'a self, col: Option<u32>) -> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a> {
-		let unboxed = Database::iter(self, col);
-		Box::new(unboxed.into_iter().flat_map(|inner| inner))
+	fn iter<'a>(&'a self, col: Option<u32>) -> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>> {
+		let unboxed = Database::iter(self, col)?;
+		Ok(Box::new(unboxed))
 	}
 
 	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
-		-> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>>
 	{
-		let unboxed = Database::iter_from_prefix(self, col, prefix);
-		Box::new(unboxed.into_iter().flat_map(|inner| inner))
+		let unboxed = Database::iter_from_prefix(self, col, prefix)?;
+		Ok(Box::new(unboxed))
+	}
+
+	fn iter_range<'a>(
+		&'a self,
+		col: Option<u32>,
+		start: Bound<&'a [u8]>,
+		end: Bound<&'a [u8]>,
+		direction: kvdb::Direction,
+	) -> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>> {
+		let unboxed = Database::iter_range(self, col, start, end, direction)?;
+		Ok(Box::new(unboxed))
+	}
+
+	fn snapshot<'a>(&'a self) -> Result<Box<dyn KeyValueDBSnapshot + 'a>> {
+		Ok(Box::new(Database::snapshot(self)?))
 	}
 
-	fn restore(&self, new_db: &str) -> io::Result<()> {
+	fn column_stats(&self, col: Option<u32>) -> Result<ColumnStats> {
+		Database::column_stats(self, col)
+	}
+
+	fn restore(&self, new_db: &str) -> Result<()> {
 		Database::restore(self, new_db)
 	}
 }
 
+impl ColumnManager for Database {
+	fn num_columns(&self) -> u32 {
+		Database::num_columns(self)
+	}
+
+	fn add_column(&self) -> Result<()> {
+		Database::add_column(self)
+	}
+
+	fn drop_column(&self) -> Result<()> {
+		Database::drop_column(self)
+	}
+}
+
 impl Drop for Database {
 	fn drop(&mut self) {
 		// write all buffered changes if we can.
@@ -706,6 +1825,7 @@ mod tests {
 	extern crate tempdir;
 
 	use std::str::FromStr;
+	use std::sync::Arc;
 	use self::tempdir::TempDir;
 	use ethereum_types::H256;
 	use super::*;
@@ -724,7 +1844,7 @@ mod tests {
 
 		assert_eq!(&*db.get(None, key1.as_bytes()).unwrap().unwrap(), b"cat");
 
-		let contents: Vec<_> = db.iter(None).into_iter().flat_map(|inner| inner).collect();
+		let contents: Vec<_> = db.iter(None).unwrap().collect();
 		assert_eq!(contents.len(), 2);
 		assert_eq!(&*contents[0].0, key1.as_bytes());
 		assert_eq!(&*contents[0].1, b"cat");
@@ -748,8 +1868,8 @@ mod tests {
 		assert!(db.get(None, key1.as_bytes()).unwrap().is_none());
 		assert_eq!(&*db.get(None, key3.as_bytes()).unwrap().unwrap(), b"elephant");
 
-		assert_eq!(&*db.get_by_prefix(None, key3.as_bytes()).unwrap(), b"elephant");
-		assert_eq!(&*db.get_by_prefix(None, key2.as_bytes()).unwrap(), b"dog");
+		assert_eq!(&*db.get_by_prefix(None, key3.as_bytes()).unwrap().unwrap(), b"elephant");
+		assert_eq!(&*db.get_by_prefix(None, key2.as_bytes()).unwrap().unwrap(), b"dog");
 
 		let mut transaction = db.transaction();
 		transaction.put(None, key1.as_bytes(), b"horse");
@@ -830,6 +1950,837 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn snapshot_is_not_affected_by_writes() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"foo", b"bar");
+		batch.put(Some(0), b"baz", b"qux");
+		db.write(batch).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"buffered", b"value");
+		db.write_buffered(batch);
+
+		{
+			let snapshot = db.snapshot().unwrap();
+
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"foo", b"changed");
+			batch.delete(Some(0), b"baz");
+			db.write(batch).unwrap();
+			db.flush().unwrap();
+
+			assert_eq!(&*snapshot.get(Some(0), b"foo").unwrap().unwrap(), b"bar");
+			assert_eq!(&*snapshot.get(Some(0), b"baz").unwrap().unwrap(), b"qux");
+			assert_eq!(&*snapshot.get(Some(0), b"buffered").unwrap().unwrap(), b"value");
+			assert_eq!(&*snapshot.get_by_prefix(Some(0), b"ba").unwrap().unwrap(), b"qux");
+			assert_eq!(snapshot.iter(Some(0)).unwrap().count(), 3);
+			// the snapshot keeps using the columns it was taken with.
+			db.add_column().unwrap();
+			assert!(snapshot.get(Some(1), b"foo").is_err());
+		}
+
+		assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), b"changed");
+		assert!(db.get(Some(0), b"baz").unwrap().is_none());
+	}
+
+	#[test]
+	fn snapshot_iter_applies_buffered_changes() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"bar", b"old");
+		batch.put(Some(0), b"baz", b"qux");
+		batch.put(Some(0), b"foo", b"bar");
+		db.write(batch).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"bar", b"new");
+		batch.delete(Some(0), b"baz");
+		batch.put(Some(0), b"bat", b"buffered");
+		db.write_buffered(batch);
+
+		let snapshot = db.snapshot().unwrap();
+		assert_eq!(snapshot.iter(Some(0)).unwrap().collect::<Vec<_>>(), vec![
+			(b"bar".to_vec().into_boxed_slice(), b"new".to_vec().into_boxed_slice()),
+			(b"bat".to_vec().into_boxed_slice(), b"buffered".to_vec().into_boxed_slice()),
+			(b"foo".to_vec().into_boxed_slice(), b"bar".to_vec().into_boxed_slice()),
+		]);
+	}
+
+	#[test]
+	fn snapshot_iter_from_prefix_includes_buffered_inserts() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"aa", b"1");
+		batch.put(Some(0), b"bb", b"2");
+		db.write(batch).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"ab", b"buffered");
+		batch.put(Some(0), b"ba", b"3");
+		db.write_buffered(batch);
+
+		let snapshot = db.snapshot().unwrap();
+		assert_eq!(snapshot.iter_from_prefix(Some(0), b"b").unwrap().collect::<Vec<_>>(), vec![
+			(b"ba".to_vec().into_boxed_slice(), b"3".to_vec().into_boxed_slice()),
+			(b"bb".to_vec().into_boxed_slice(), b"2".to_vec().into_boxed_slice()),
+		]);
+		assert_eq!(snapshot.iter_from_prefix(Some(0), b"a").unwrap().count(), 4);
+	}
+
+	#[test]
+	fn column_stats() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		for i in 0u32..100 {
+			batch.put(Some(0), &i.to_be_bytes(), b"value");
+		}
+		db.write(batch).unwrap();
+
+		let stats = db.column_stats(Some(0)).unwrap();
+		assert!(stats.num_keys > 0);
+		assert!(stats.memtable_size > 0);
+		assert_eq!(db.column_stats(None).unwrap().num_keys, 0);
+		assert!(db.column_stats(Some(1)).is_err());
+	}
+
+	#[test]
+	fn io_stats() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"foo", b"bar");
+		batch.put(Some(0), b"baz", b"quux");
+		db.write(batch).unwrap();
+
+		let before = db.io_stats();
+		assert_eq!(before.transactions, 1);
+		assert_eq!(before.writes, 2);
+		assert_eq!(before.bytes_written, 13);
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"buffered", b"value");
+		db.write_buffered(batch);
+		assert_eq!(&*db.get(Some(0), b"buffered").unwrap().unwrap(), b"value");
+		assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), b"bar");
+		assert!(db.get(Some(0), b"missing").unwrap().is_none());
+		db.flush().unwrap();
+
+		let diff = db.io_stats().since(&before);
+		assert_eq!(diff.transactions, 1);
+		assert_eq!(diff.reads, 3);
+		assert_eq!(diff.cache_reads, 1);
+		assert_eq!(diff.bytes_read, 8);
+		assert_eq!(diff.writes, 1);
+		assert_eq!(diff.bytes_written, 13);
+		assert_eq!(diff.delayed_write_rate, 0);
+	}
+
+	#[test]
+	fn statistics() {
+		let tempdir = TempDir::new("").unwrap();
+		let path = tempdir.path().to_str().unwrap();
+		let mut config = DatabaseConfig::with_columns(Some(1));
+
+		{
+			let db = Database::open(&config, path).unwrap();
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"foo", b"bar");
+			db.write(batch).unwrap();
+			assert!(db.get(Some(0), b"foo").unwrap().is_some());
+			assert_eq!(db.io_stats().block_cache_misses, 0);
+		}
+
+		// the write is flushed to a table file when the database is reopened.
+		config.enable_statistics = true;
+		let db = Database::open(&config, path).unwrap();
+		let before = db.io_stats();
+		assert!(db.get(Some(0), b"foo").unwrap().is_some());
+		let diff = db.io_stats().since(&before);
+		assert!(diff.block_cache_hits + diff.block_cache_misses > 0);
+	}
+
+	#[test]
+	fn ticker() {
+		let statistics = "rocksdb.block.cache.miss COUNT : 12\nrocksdb.block.cache.hit COUNT : 7\n\
+			rocksdb.db.get.micros P50 : 1.000000 P95 : 2.000000 COUNT : 4 SUM : 5\n";
+		assert_eq!(super::ticker(statistics, "rocksdb.block.cache.hit"), 7);
+		assert_eq!(super::ticker(statistics, "rocksdb.block.cache.miss"), 12);
+		assert_eq!(super::ticker(statistics, "rocksdb.stall.micros"), 0);
+	}
+
+	#[test]
+	fn column_config() {
+		let tempdir = TempDir::new("").unwrap();
+		let mut config = DatabaseConfig::with_columns(Some(2));
+		config.memory_budget = Some(64);
+		config.column_config.insert(Some(0), ColumnConfig {
+			memory_budget: Some(48),
+			block_size: Some(4 * KB),
+			bloom_filter_bits: Some(10),
+			prefix_extractor_len: Some(4),
+			compression_per_level: Some(vec![Compression::None, Compression::Snappy]),
+		});
+		assert_eq!(config.memory_budget_for_col(Some(0)), 48 * MB);
+		// the rest of the budget goes to the other column.
+		assert_eq!(config.memory_budget_for_col(Some(1)), 16 * MB);
+		config.column_config.insert(None, ColumnConfig { bloom_filter_bits: Some(10), ..Default::default() });
+
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+		let mut batch = db.transaction();
+		batch.put(None, b"key1", b"baz");
+		batch.put(Some(0), b"key1", b"foo");
+		batch.put(Some(1), b"key1", b"bar");
+		db.write(batch).unwrap();
+
+		assert_eq!(&*db.get(Some(0), b"key1").unwrap().unwrap(), b"foo");
+		assert_eq!(&*db.get(Some(1), b"key1").unwrap().unwrap(), b"bar");
+		assert_eq!(&*db.get(None, b"key1").unwrap().unwrap(), b"baz");
+		assert_eq!(&*db.get_by_prefix(Some(0), b"key").unwrap().unwrap(), b"foo");
+	}
+
+	#[test]
+	fn compression() {
+		assert_eq!(compression_options(&[Compression::None]).unwrap(), "compression_per_level=kNoCompression");
+		assert_eq!(
+			compression_options(&[Compression::None, Compression::Snappy]).unwrap(),
+			"compression_per_level=kNoCompression:kSnappyCompression",
+		);
+		assert!(compression_options(&[Compression::None, Compression::Lz4]).is_err());
+
+		let tempdir = TempDir::new("").unwrap();
+		let mut config = DatabaseConfig::with_columns(Some(1));
+		config.compaction.compression = Compression::Zstd(3);
+		assert!(Database::open(&config, tempdir.path().to_str().unwrap()).is_err());
+	}
+
+	#[test]
+	fn read_only() {
+		let tempdir = TempDir::new("").unwrap();
+		let path = tempdir.path().join("db");
+		let path = path.to_str().unwrap();
+		let mut config = DatabaseConfig::with_columns(Some(1));
+		config.open_mode = OpenMode::ReadOnly;
+		assert!(Database::open(&config, path).is_err());
+		assert!(!Path::new(path).exists());
+
+		let writer = Database::open(&DatabaseConfig::with_columns(Some(1)), path).unwrap();
+		let mut batch = writer.transaction();
+		batch.put(Some(0), b"foo", b"bar");
+		writer.write(batch).unwrap();
+
+		// the database can be read while it is open for writing.
+		let db = Database::open(&config, path).unwrap();
+		assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), b"bar");
+		drop(writer);
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"foo", b"baz");
+		match db.write(batch.clone()) {
+			Err(Error::ReadOnly) => {},
+			_ => panic!("expected a read-only error"),
+		}
+		db.flush().unwrap();
+		assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), b"bar");
+		assert!(db.add_column().is_err());
+	}
+
+	#[test]
+	fn read_only_write_buffered_fails_flush() {
+		let tempdir = TempDir::new("").unwrap();
+		let path = tempdir.path().to_str().unwrap();
+		drop(Database::open(&DatabaseConfig::with_columns(Some(1)), path).unwrap());
+
+		let mut config = DatabaseConfig::with_columns(Some(1));
+		config.open_mode = OpenMode::ReadOnly;
+		let db = Database::open(&config, path).unwrap();
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"foo", b"bar");
+		db.write_buffered(batch);
+		assert!(db.get(Some(0), b"foo").unwrap().is_none());
+		match db.flush() {
+			Err(Error::ReadOnly) => {},
+			_ => panic!("expected a read-only error"),
+		}
+		// the error is reported once.
+		db.flush().unwrap();
+	}
+
+	#[test]
+	fn compaction() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		db.pause_compactions().unwrap();
+		assert!(db.compactions_paused());
+		db.add_column().unwrap();
+
+		let mut batch = db.transaction();
+		for i in 0u32..1000 {
+			batch.put(Some(0), &i.to_be_bytes(), b"value");
+		}
+		db.write(batch).unwrap();
+
+		let mut batch = db.transaction();
+		batch.delete_prefix(Some(0), &[0, 0]);
+		db.write_buffered(batch);
+
+		db.compact_range(Some(0), Some(&[0, 0, 0, 0]), Some(&[0, 0, 1, 0])).unwrap();
+		db.compact_column(Some(0)).unwrap();
+		db.compact_column(None).unwrap();
+		assert!(db.compact_column(Some(2)).is_err());
+		assert_eq!(db.iter(Some(0)).unwrap().count(), 0);
+
+		// the buffered changes are kept when the database is reopened.
+		let mut batch = db.transaction();
+		batch.put(Some(1), b"foo", b"bar");
+		db.write_buffered(batch);
+		{
+			let _snapshot = db.snapshot().unwrap();
+			assert!(db.resume_compactions().is_err());
+		}
+		db.resume_compactions().unwrap();
+		assert!(!db.compactions_paused());
+		assert_eq!(&*db.get(Some(1), b"foo").unwrap().unwrap(), b"bar");
+		assert_eq!(db.num_columns(), 2);
+	}
+
+	#[test]
+	fn compaction_of_default_column() {
+		let tempdir = TempDir::new("").unwrap();
+		let db = Database::open(&DatabaseConfig::default(), tempdir.path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(None, b"foo", b"bar");
+		db.write(batch).unwrap();
+
+		// the options of the default column are changed without reopening the database.
+		let snapshot = db.snapshot().unwrap();
+		db.pause_compactions().unwrap();
+		assert!(db.compactions_paused());
+		db.compact_column(None).unwrap();
+		db.resume_compactions().unwrap();
+		assert!(!db.compactions_paused());
+		assert_eq!(&*snapshot.get(None, b"foo").unwrap().unwrap(), b"bar");
+	}
+
+	#[test]
+	fn migration() {
+		use kvdb::migration::{self, AddColumn, Manager, RewriteColumn};
+
+		let tempdir = TempDir::new("").unwrap();
+		let path = tempdir.path().to_str().unwrap();
+
+		let mut manager = Manager::new();
+		manager.add_migration(AddColumn { version: 1, column: 1 }).unwrap();
+		manager.add_migration(RewriteColumn {
+			version: 2,
+			from: Some(0),
+			to: Some(1),
+			batch_size: 3,
+			rewrite: |key: &[u8], value: &[u8]| Some((key.to_vec(), vec![value[0] * 2])),
+		}).unwrap();
+
+		{
+			let db = Database::open(&DatabaseConfig::with_columns(Some(1)), path).unwrap();
+			let mut batch = db.transaction();
+			for i in 0u8..10 {
+				batch.put(Some(0), &[i], &[i]);
+			}
+			db.write(batch).unwrap();
+			assert_eq!(manager.migrate(&db).unwrap(), 2);
+		}
+
+		let db = Database::open(&DatabaseConfig::with_columns(Some(2)), path).unwrap();
+		assert_eq!(migration::version(&db).unwrap(), 2);
+		assert!(!manager.is_needed(&db).unwrap());
+		assert_eq!(db.iter(Some(1)).unwrap().count(), 10);
+		assert_eq!(&*db.get(Some(1), &[7]).unwrap().unwrap(), &[14]);
+		assert!(db.get(None, migration::PROGRESS_KEY).unwrap().is_none());
+	}
+
+	#[test]
+	fn named_columns() {
+		let tempdir = TempDir::new("").unwrap();
+		let path = tempdir.path().to_str().unwrap();
+
+		{
+			let db = Database::open(&DatabaseConfig::with_columns(Some(2)), path).unwrap();
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"foo", b"state");
+			batch.put(Some(1), b"foo", b"headers");
+			db.write(batch).unwrap();
+		}
+
+		let config = DatabaseConfig::with_named_columns(&["state", "headers"]);
+		let db = Database::open(&config, path).unwrap();
+		let state = db.column_id("state").unwrap();
+		let headers = db.column_id("headers").unwrap();
+		assert_eq!(state, ColumnId::new(0));
+		assert!(db.column_id("receipts").is_none());
+
+		assert_eq!(&*db.get(state.into(), b"foo").unwrap().unwrap(), b"state");
+		assert_eq!(&*db.get(headers.into(), b"foo").unwrap().unwrap(), b"headers");
+
+		let mut batch = db.transaction();
+		batch.put(headers, b"bar", b"baz");
+		db.write(batch).unwrap();
+		drop(db);
+
+		// the columns are only migrated once.
+		let db = Database::open(&config, path).unwrap();
+		assert_eq!(&*db.get(Some(1), b"bar").unwrap().unwrap(), b"baz");
+	}
+
+	#[test]
+	fn interrupted_column_migration_is_resumed() {
+		let tempdir = TempDir::new("").unwrap();
+		let path = tempdir.path().to_str().unwrap();
+
+		{
+			let db = Database::open(&DatabaseConfig::with_columns(Some(2)), path).unwrap();
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"foo", b"state");
+			batch.put(Some(1), b"foo", b"headers");
+			db.write(batch).unwrap();
+		}
+
+		// interrupt the migration while copying the second column.
+		{
+			let cfnames = ["col0", "col1"];
+			let (db, cfs) = DB::open(&Options::new(), path, &cfnames, &[Options::new(), Options::new()]).unwrap();
+			let mut batch = WriteBatch::new();
+			let state = db.create_cf("state", &Options::new()).unwrap();
+			batch.put(&state, b"foo", b"state");
+			db.drop_cf(&cfs[0]).unwrap();
+			let headers = db.create_cf("headers", &Options::new()).unwrap();
+			batch.put(&headers, b"partial", b"copy");
+			db.write(&batch, &WriteOptions::new()).unwrap();
+			fs::write(tempdir.path().join(Database::MIGRATION_FILE_NAME), b"1").unwrap();
+		}
+
+		let config = DatabaseConfig::with_named_columns(&["state", "headers"]);
+		let db = Database::open(&config, path).unwrap();
+		assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), b"state");
+		assert_eq!(&*db.get(Some(1), b"foo").unwrap().unwrap(), b"headers");
+		assert!(db.get(Some(1), b"partial").unwrap().is_none());
+		assert!(!tempdir.path().join(Database::MIGRATION_FILE_NAME).exists());
+	}
+
+	#[test]
+	fn flush_sequence() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+		assert_eq!(db.last_flushed_sequence(), 0);
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"foo", b"bar");
+		let first = db.write_buffered(batch);
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"baz", b"qux");
+		let second = db.write_buffered(batch);
+		assert_eq!(first, 1);
+		assert_eq!(second, 2);
+		assert_eq!(db.last_flushed_sequence(), 0);
+
+		// all the buffered transactions are flushed.
+		db.flush().unwrap();
+		assert_eq!(db.last_flushed_sequence(), second);
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"foo", b"changed");
+		let third = db.write_buffered(batch);
+		assert_eq!(db.last_flushed_sequence(), second);
+		db.flush().unwrap();
+		assert_eq!(db.last_flushed_sequence(), third);
+
+		// transactions written without the WAL are not durable.
+		let mut batch = db.transaction();
+		batch.set_durability(Durability::NoWal);
+		batch.put(Some(0), b"foo", b"cached");
+		db.write_buffered(batch);
+		db.flush().unwrap();
+		assert_eq!(db.last_flushed_sequence(), third);
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"foo", b"logged");
+		let fifth = db.write_buffered(batch);
+		db.flush().unwrap();
+		assert_eq!(db.last_flushed_sequence(), fifth);
+	}
+
+	#[test]
+	fn durability() {
+		let tempdir = TempDir::new("").unwrap();
+		let path = tempdir.path().to_str().unwrap();
+		let mut config = DatabaseConfig::with_columns(Some(1));
+		config.durability = Durability::WalSynced;
+		config.use_fsync = true;
+
+		{
+			let db = Database::open(&config, path).unwrap();
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"synced", b"value");
+			db.write(batch).unwrap();
+
+			let mut batch = db.transaction();
+			batch.set_durability(Durability::NoWal);
+			batch.put(Some(0), b"cache", b"value");
+			db.write(batch).unwrap();
+			assert_eq!(&*db.get(Some(0), b"cache").unwrap().unwrap(), b"value");
+
+			let mut batch = db.transaction();
+			batch.set_durability(Durability::NoWal);
+			batch.put(Some(0), b"buffered", b"value");
+			db.write_buffered(batch);
+			assert_eq!(*db.overlay_durability.lock(), Some(Durability::NoWal));
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"buffered", b"synced");
+			db.write_buffered(batch);
+			assert_eq!(*db.overlay_durability.lock(), Some(Durability::WalSynced));
+			db.flush().unwrap();
+			assert_eq!(*db.overlay_durability.lock(), None);
+		}
+
+		let db = Database::open(&config, path).unwrap();
+		assert_eq!(&*db.get(Some(0), b"synced").unwrap().unwrap(), b"value");
+		assert_eq!(&*db.get(Some(0), b"buffered").unwrap().unwrap(), b"synced");
+	}
+
+	#[test]
+	fn merge() {
+		let tempdir = TempDir::new("").unwrap();
+		let path = tempdir.path().to_str().unwrap();
+		let mut config = DatabaseConfig::with_columns(Some(1));
+		let counter: MergeFn = Arc::new(|_key: &[u8], existing: Option<&[u8]>, operands: &[&[u8]]| {
+			let count = existing.map_or(0, |v| v[0]) + operands.iter().map(|op| op[0]).sum::<u8>();
+			vec![count]
+		});
+		config.merge_operators.insert(Some(0), counter);
+
+		{
+			let db = Database::open(&config, path).unwrap();
+			let mut batch = db.transaction();
+			batch.merge(Some(0), b"foo", &[1]);
+			batch.merge(Some(0), b"foo", &[2]);
+			db.write(batch).unwrap();
+			assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), &[3]);
+
+			// buffered merges are applied to the value in RocksDB when read.
+			let mut batch = db.transaction();
+			batch.merge(Some(0), b"foo", &[1]);
+			batch.put(Some(0), b"bar", &[5]);
+			batch.merge(Some(0), b"bar", &[1]);
+			db.write_buffered(batch);
+			assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), &[4]);
+			assert_eq!(&*db.get(Some(0), b"bar").unwrap().unwrap(), &[6]);
+			assert_eq!(db.get_many(Some(0), &[b"foo", b"bar"]).unwrap(), vec![Some(DBValue::from_slice(&[4])), Some(DBValue::from_slice(&[6]))]);
+			assert_eq!(db.iter(Some(0)).unwrap().collect::<Vec<_>>(), vec![
+				(b"bar".to_vec().into_boxed_slice(), vec![6].into_boxed_slice()),
+				(b"foo".to_vec().into_boxed_slice(), vec![4].into_boxed_slice()),
+			]);
+
+			// a merge into a buffered value is applied after it.
+			let mut batch = db.transaction();
+			batch.merge(Some(0), b"bar", &[2]);
+			db.write(batch).unwrap();
+
+			let snapshot = db.snapshot().unwrap();
+			assert_eq!(&*snapshot.get(Some(0), b"foo").unwrap().unwrap(), &[4]);
+			assert_eq!(&*snapshot.get(Some(0), b"bar").unwrap().unwrap(), &[8]);
+			drop(snapshot);
+
+			db.flush().unwrap();
+			assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), &[4]);
+			assert_eq!(&*db.get(Some(0), b"bar").unwrap().unwrap(), &[8]);
+
+			// RocksDB iterators apply the stored operands as well.
+			assert_eq!(db.iter(Some(0)).unwrap().collect::<Vec<_>>(), vec![
+				(b"bar".to_vec().into_boxed_slice(), vec![8].into_boxed_slice()),
+				(b"foo".to_vec().into_boxed_slice(), vec![4].into_boxed_slice()),
+			]);
+
+			// no merge operator registered.
+			let mut batch = db.transaction();
+			batch.merge(None, b"foo", &[1]);
+			assert!(db.write(batch).is_err());
+
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"baz", &[1]);
+			batch.merge(None, b"foo", &[1]);
+			db.write_buffered(batch);
+			assert!(db.get(Some(0), b"baz").unwrap().is_none());
+			assert!(db.flush().is_err());
+			db.flush().unwrap();
+		}
+
+		let db = Database::open(&config, path).unwrap();
+		assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), &[4]);
+		assert_eq!(&*db.get(Some(0), b"bar").unwrap().unwrap(), &[8]);
+	}
+
+	#[test]
+	fn checkpoint() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().join("db").to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"foo", b"bar");
+		db.write_buffered(batch);
+
+		let checkpoint_path = tempdir.path().join("checkpoint");
+		db.checkpoint(checkpoint_path.to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"foo", b"baz");
+		db.write(batch).unwrap();
+
+		let checkpoint = Database::open(&config, checkpoint_path.to_str().unwrap()).unwrap();
+		assert_eq!(&*checkpoint.get(Some(0), b"foo").unwrap().unwrap(), b"bar");
+	}
+
+	#[test]
+	fn backup_and_restore() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let path = tempdir.path().join("db");
+		let path = path.to_str().unwrap();
+		let backup_dir = tempdir.path().join("backup");
+		let backup_dir = backup_dir.to_str().unwrap();
+
+		{
+			let db = Database::open(&config, path).unwrap();
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"foo", b"bar");
+			db.write(batch).unwrap();
+			let first = db.backup(backup_dir).unwrap();
+
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"foo", b"baz");
+			db.write_buffered(batch);
+			let second = db.backup(backup_dir).unwrap();
+			assert!(second > first);
+
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"foo", b"qux");
+			batch.put(Some(0), b"new", b"value");
+			db.write(batch).unwrap();
+
+			// only the latest backup can be restored.
+			assert!(db.restore_from_backup(backup_dir, first).is_err());
+			db.restore_from_backup(backup_dir, second).unwrap();
+			assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), b"baz");
+			assert!(db.get(Some(0), b"new").unwrap().is_none());
+		}
+
+		let db = Database::open(&config, path).unwrap();
+		assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), b"baz");
+		assert!(db.get(Some(0), b"new").unwrap().is_none());
+	}
+
+	#[test]
+	fn bulk_loader() {
+		let tempdir = TempDir::new("").unwrap();
+		let path = tempdir.path().to_str().unwrap();
+		let config = DatabaseConfig::with_columns(Some(2));
+
+		{
+			let db = Database::open(&config, path).unwrap();
+			let mut batch = db.transaction();
+			batch.put(Some(0), &5u32.to_be_bytes(), b"buffered");
+			batch.put(Some(0), b"other", b"value");
+			db.write_buffered(batch);
+
+			let mut loader = db.bulk_loader().unwrap();
+			for i in 0u32..1000 {
+				loader.put(Some(0), &i.to_be_bytes(), &i.to_le_bytes()).unwrap();
+			}
+			loader.put(None, b"foo", b"bar").unwrap();
+			assert!(loader.put(Some(0), &3u32.to_be_bytes(), b"").is_err());
+			assert!(loader.put(Some(2), b"foo", b"bar").is_err());
+			loader.finish().unwrap();
+
+			assert_eq!(&*db.get(Some(0), &5u32.to_be_bytes()).unwrap().unwrap(), &5u32.to_le_bytes());
+			assert_eq!(&*db.get(Some(0), b"other").unwrap().unwrap(), b"value");
+			assert_eq!(&*db.get(None, b"foo").unwrap().unwrap(), b"bar");
+			assert_eq!(db.iter(Some(0)).unwrap().count(), 1001);
+			assert!(db.iter(Some(1)).unwrap().next().is_none());
+		}
+
+		// the ingested files are part of the database.
+		let db = Database::open(&config, path).unwrap();
+		assert_eq!(&*db.get(Some(0), &999u32.to_be_bytes()).unwrap().unwrap(), &999u32.to_le_bytes());
+		assert_eq!(db.iter(Some(0)).unwrap().count(), 1001);
+		assert!(!Path::new(&format!("{}.bulk", path)).exists());
+	}
+
+	#[test]
+	fn unknown_column() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		match db.get(Some(1), b"foo") {
+			Err(Error::UnknownColumn(Some(1))) => {},
+			_ => panic!("expected an unknown column error"),
+		}
+		assert!(db.iter(Some(1)).is_err());
+
+		let mut batch = db.transaction();
+		batch.put(Some(1), b"foo", b"bar");
+		assert!(db.write(batch).is_err());
+	}
+
+	#[test]
+	fn iter_range() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::default();
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(None, &[1], b"one");
+		batch.put(None, &[3], b"three");
+		batch.put(None, &[5], b"five");
+		db.write(batch).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(None, &[4], b"four");
+		db.write_buffered(batch);
+
+		let keys = |start, end, direction| db.iter_range(None, start, end, direction)
+			.unwrap()
+			.map(|(k, _)| k.into_vec())
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			keys(Bound::Included(&[3][..]), Bound::Excluded(&[5][..]), kvdb::Direction::Forward),
+			vec![vec![3], vec![4]],
+		);
+		assert_eq!(
+			keys(Bound::Excluded(&[1][..]), Bound::Unbounded, kvdb::Direction::Forward),
+			vec![vec![3], vec![4], vec![5]],
+		);
+		// latest key less than or equal to 2.
+		assert_eq!(
+			keys(Bound::Unbounded, Bound::Included(&[2][..]), kvdb::Direction::Reverse),
+			vec![vec![1]],
+		);
+		assert_eq!(
+			keys(Bound::Included(&[3][..]), Bound::Included(&[5][..]), kvdb::Direction::Reverse),
+			vec![vec![5], vec![4], vec![3]],
+		);
+		assert_eq!(
+			keys(Bound::Unbounded, Bound::Excluded(&[5][..]), kvdb::Direction::Reverse),
+			vec![vec![4], vec![3], vec![1]],
+		);
+		// the end bound is past the last key.
+		assert_eq!(
+			keys(Bound::Included(&[3][..]), Bound::Included(&[9][..]), kvdb::Direction::Reverse),
+			vec![vec![5], vec![4], vec![3]],
+		);
+		// the seek lands on the excluded end key, which is skipped.
+		assert_eq!(
+			keys(Bound::Included(&[1][..]), Bound::Excluded(&[3][..]), kvdb::Direction::Reverse),
+			vec![vec![1]],
+		);
+		// the excluded end key is only buffered.
+		assert_eq!(
+			keys(Bound::Excluded(&[1][..]), Bound::Excluded(&[4][..]), kvdb::Direction::Reverse),
+			vec![vec![3]],
+		);
+	}
+
+	#[test]
+	fn delete_prefix() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		for key in &[&[0][..], &[1, 0], &[1, 255], &[1, 255, 255], &[2]] {
+			batch.put(Some(0), key, key);
+		}
+		db.write(batch).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), &[1, 2], &[1, 2]);
+		batch.delete_prefix(Some(0), &[1]);
+		batch.put(Some(0), &[1, 1], &[1, 1]);
+		db.write_buffered(batch);
+
+		let keys = |db: &Database| db.iter(Some(0))
+			.unwrap()
+			.map(|(k, _)| k.into_vec())
+			.collect::<Vec<_>>();
+
+		// deletion is visible before and after flushing.
+		assert!(db.get(Some(0), &[1, 255]).unwrap().is_none());
+		assert!(db.get(Some(0), &[1, 2]).unwrap().is_none());
+		assert_eq!(&*db.get(Some(0), &[1, 1]).unwrap().unwrap(), &[1, 1]);
+		assert_eq!(keys(&db), vec![vec![0], vec![1, 1], vec![2]]);
+
+		db.flush().unwrap();
+		assert!(db.get(Some(0), &[1, 255]).unwrap().is_none());
+		assert_eq!(&*db.get(Some(0), &[1, 1]).unwrap().unwrap(), &[1, 1]);
+		assert_eq!(keys(&db), vec![vec![0], vec![1, 1], vec![2]]);
+
+		// keys written earlier in the same transaction are deleted too.
+		let mut batch = db.transaction();
+		batch.put(Some(0), &[1, 3], &[1, 3]);
+		batch.delete_prefix(Some(0), &[1]);
+		batch.put(Some(0), &[1, 4], &[1, 4]);
+		db.write(batch).unwrap();
+		assert_eq!(keys(&db), vec![vec![0], vec![1, 4], vec![2]]);
+
+		let mut batch = db.transaction();
+		batch.delete_prefix(Some(0), &[]);
+		db.write(batch).unwrap();
+		assert!(keys(&db).is_empty());
+	}
+
+	#[test]
+	fn get_many() {
+		let tempdir = TempDir::new("").unwrap();
+		let config = DatabaseConfig::with_columns(Some(1));
+		let db = Database::open(&config, tempdir.path().to_str().unwrap()).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"flushed", b"1");
+		batch.put(Some(0), b"deleted", b"2");
+		db.write(batch).unwrap();
+
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"buffered", b"3");
+		batch.delete(Some(0), b"deleted");
+		db.write_buffered(batch);
+
+		let keys: &[&[u8]] = &[b"buffered", b"missing", b"flushed", b"deleted"];
+		let values = db.get_many(Some(0), keys).unwrap();
+		assert_eq!(values.len(), 4);
+		assert_eq!(values[0].as_ref().map(|v| &v[..]), Some(&b"3"[..]));
+		assert!(values[1].is_none());
+		assert_eq!(values[2].as_ref().map(|v| &v[..]), Some(&b"1"[..]));
+		assert!(values[3].is_none());
+	}
+
 	#[test]
 	fn write_clears_buffered_ops() {
 		let tempdir = TempDir::new("").unwrap();
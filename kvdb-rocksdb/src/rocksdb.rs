@@ -0,0 +1,1149 @@
+// Copyright 2015-2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bindings to the RocksDB C API, linked through `parity-rocksdb-sys`.
+//!
+//! The database is reference counted: column family handles, snapshots and iterators keep
+//! it open until they are dropped, so they don't borrow from it.
+
+use std::{
+	fs, iter, ptr, slice,
+	ffi::{CStr, CString},
+	path::Path,
+	sync::Arc,
+};
+
+use libc::{c_char, c_int, c_uchar, c_void, size_t};
+
+// Take the error reported by RocksDB through an error pointer.
+fn check_error(err: *mut c_char) -> Result<(), String> {
+	if err.is_null() {
+		return Ok(());
+	}
+	let message = unsafe { CStr::from_ptr(err) }.to_string_lossy().into_owned();
+	unsafe { ffi::rocksdb_free(err as *mut c_void) };
+	Err(message)
+}
+
+fn to_cstring(s: &str) -> Result<CString, String> {
+	CString::new(s).map_err(|_| format!("Invalid string passed to RocksDB: {:?}", s))
+}
+
+/// Options of the database or of a column family.
+pub struct Options {
+	inner: *mut ffi::rocksdb_options_t,
+}
+
+unsafe impl Send for Options {}
+unsafe impl Sync for Options {}
+
+impl Options {
+	pub fn new() -> Options {
+		Options { inner: unsafe { ffi::rocksdb_options_create() } }
+	}
+
+	/// Set options from a string like `"keep_log_file_num=1;bytes_per_sync=1048576"`.
+	pub fn set_parsed_options(&mut self, opts: &str) -> Result<(), String> {
+		let opts = to_cstring(opts)?;
+		let mut err = ptr::null_mut();
+		unsafe {
+			let new_inner = ffi::rocksdb_options_create();
+			ffi::rocksdb_get_options_from_string(self.inner, opts.as_ptr(), new_inner, &mut err);
+			if let Err(e) = check_error(err) {
+				ffi::rocksdb_options_destroy(new_inner);
+				return Err(e);
+			}
+			ffi::rocksdb_options_destroy(self.inner);
+			self.inner = new_inner;
+		}
+		Ok(())
+	}
+
+	pub fn create_if_missing(&mut self, create: bool) {
+		unsafe { ffi::rocksdb_options_set_create_if_missing(self.inner, create as c_uchar) }
+	}
+
+	/// Collect statistics of the databases opened with these options.
+	pub fn enable_statistics(&mut self) {
+		unsafe { ffi::rocksdb_options_enable_statistics(self.inner) }
+	}
+
+	/// The statistics collected since the database was opened, one per line,
+	/// or `None` if they are not enabled.
+	pub fn statistics(&self) -> Option<String> {
+		let stats = unsafe { ffi::rocksdb_options_statistics_get_string(self.inner) };
+		if stats.is_null() {
+			return None;
+		}
+		let owned = unsafe { CStr::from_ptr(stats) }.to_string_lossy().into_owned();
+		unsafe { ffi::rocksdb_free(stats as *mut c_void) };
+		Some(owned)
+	}
+
+	pub fn set_use_fsync(&mut self, use_fsync: bool) {
+		unsafe { ffi::rocksdb_options_set_use_fsync(self.inner, use_fsync as c_int) }
+	}
+
+	pub fn set_max_open_files(&mut self, max_open_files: i32) {
+		unsafe { ffi::rocksdb_options_set_max_open_files(self.inner, max_open_files) }
+	}
+
+	pub fn set_db_write_buffer_size(&mut self, size: usize) {
+		unsafe { ffi::rocksdb_options_set_db_write_buffer_size(self.inner, size) }
+	}
+
+	pub fn increase_parallelism(&mut self, threads: i32) {
+		unsafe { ffi::rocksdb_options_increase_parallelism(self.inner, threads) }
+	}
+
+	pub fn optimize_level_style_compaction(&mut self, memtable_memory_budget: usize) {
+		unsafe { ffi::rocksdb_options_optimize_level_style_compaction(self.inner, memtable_memory_budget as u64) }
+	}
+
+	pub fn set_target_file_size_base(&mut self, size: u64) {
+		unsafe { ffi::rocksdb_options_set_target_file_size_base(self.inner, size) }
+	}
+
+	pub fn set_disable_auto_compactions(&mut self, disable: bool) {
+		unsafe { ffi::rocksdb_options_set_disable_auto_compactions(self.inner, disable as c_int) }
+	}
+
+	pub fn set_block_based_table_factory(&mut self, block_opts: &BlockBasedOptions) {
+		unsafe { ffi::rocksdb_options_set_block_based_table_factory(self.inner, block_opts.inner) }
+	}
+
+	/// Apply the operands written with `WriteBatch::merge` with `merge`, which gets the key,
+	/// its existing value if any and the operands, oldest first, and returns the new value.
+	pub fn set_merge_operator<F>(&mut self, merge: F)
+		where F: Fn(&[u8], Option<&[u8]>, &[&[u8]]) -> Vec<u8> + Send + Sync + 'static
+	{
+		let state = Box::into_raw(Box::new(merge)) as *mut c_void;
+		unsafe {
+			let operator = ffi::rocksdb_mergeoperator_create(
+				state,
+				merge_operator::destroy::<F>,
+				merge_operator::full_merge::<F>,
+				merge_operator::partial_merge,
+				merge_operator::delete_value,
+				merge_operator::name,
+			);
+			// the options take ownership of the operator.
+			ffi::rocksdb_options_set_merge_operator(self.inner, operator);
+		}
+	}
+}
+
+impl Drop for Options {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_options_destroy(self.inner) }
+	}
+}
+
+/// Options of the block based SST file format.
+pub struct BlockBasedOptions {
+	inner: *mut ffi::rocksdb_block_based_table_options_t,
+}
+
+unsafe impl Send for BlockBasedOptions {}
+unsafe impl Sync for BlockBasedOptions {}
+
+impl BlockBasedOptions {
+	pub fn new() -> BlockBasedOptions {
+		BlockBasedOptions { inner: unsafe { ffi::rocksdb_block_based_options_create() } }
+	}
+
+	pub fn set_block_size(&mut self, size: usize) {
+		unsafe { ffi::rocksdb_block_based_options_set_block_size(self.inner, size) }
+	}
+
+	/// Use the given block cache, which is shared with the other users of `cache`.
+	pub fn set_cache(&mut self, cache: Cache) {
+		unsafe { ffi::rocksdb_block_based_options_set_block_cache(self.inner, cache.inner) }
+	}
+}
+
+impl Drop for BlockBasedOptions {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_block_based_options_destroy(self.inner) }
+	}
+}
+
+/// LRU block cache. RocksDB keeps it alive for as long as options use it.
+pub struct Cache {
+	inner: *mut ffi::rocksdb_cache_t,
+}
+
+impl Cache {
+	pub fn new(capacity: usize) -> Cache {
+		Cache { inner: unsafe { ffi::rocksdb_cache_create_lru(capacity) } }
+	}
+}
+
+impl Drop for Cache {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_cache_destroy(self.inner) }
+	}
+}
+
+pub struct ReadOptions {
+	inner: *mut ffi::rocksdb_readoptions_t,
+}
+
+unsafe impl Send for ReadOptions {}
+unsafe impl Sync for ReadOptions {}
+
+impl ReadOptions {
+	pub fn new() -> ReadOptions {
+		ReadOptions { inner: unsafe { ffi::rocksdb_readoptions_create() } }
+	}
+
+	pub fn set_verify_checksums(&mut self, verify: bool) {
+		unsafe { ffi::rocksdb_readoptions_set_verify_checksums(self.inner, verify as c_uchar) }
+	}
+}
+
+impl Drop for ReadOptions {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_readoptions_destroy(self.inner) }
+	}
+}
+
+pub struct WriteOptions {
+	inner: *mut ffi::rocksdb_writeoptions_t,
+}
+
+impl WriteOptions {
+	pub fn new() -> WriteOptions {
+		WriteOptions { inner: unsafe { ffi::rocksdb_writeoptions_create() } }
+	}
+
+	pub fn disable_wal(&mut self, disable: bool) {
+		unsafe { ffi::rocksdb_writeoptions_disable_WAL(self.inner, disable as c_int) }
+	}
+
+	pub fn set_sync(&mut self, sync: bool) {
+		unsafe { ffi::rocksdb_writeoptions_set_sync(self.inner, sync as c_uchar) }
+	}
+}
+
+impl Drop for WriteOptions {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_writeoptions_destroy(self.inner) }
+	}
+}
+
+/// Changes written atomically with `DB::write`.
+pub struct WriteBatch {
+	inner: *mut ffi::rocksdb_writebatch_t,
+}
+
+impl WriteBatch {
+	pub fn new() -> WriteBatch {
+		WriteBatch { inner: unsafe { ffi::rocksdb_writebatch_create() } }
+	}
+
+	pub fn put(&mut self, cf: &Column, key: &[u8], value: &[u8]) {
+		unsafe {
+			ffi::rocksdb_writebatch_put_cf(
+				self.inner, cf.raw(),
+				key.as_ptr() as *const c_char, key.len(),
+				value.as_ptr() as *const c_char, value.len(),
+			)
+		}
+	}
+
+	pub fn delete(&mut self, cf: &Column, key: &[u8]) {
+		unsafe { ffi::rocksdb_writebatch_delete_cf(self.inner, cf.raw(), key.as_ptr() as *const c_char, key.len()) }
+	}
+
+	/// Add a merge operand for `key`, applied by the merge operator of the column family.
+	pub fn merge(&mut self, cf: &Column, key: &[u8], operand: &[u8]) {
+		unsafe {
+			ffi::rocksdb_writebatch_merge_cf(
+				self.inner, cf.raw(),
+				key.as_ptr() as *const c_char, key.len(),
+				operand.as_ptr() as *const c_char, operand.len(),
+			)
+		}
+	}
+
+	/// Delete the keys from `start` up to but excluding `end`.
+	pub fn delete_range(&mut self, cf: &Column, start: &[u8], end: &[u8]) {
+		unsafe {
+			ffi::rocksdb_writebatch_delete_range_cf(
+				self.inner, cf.raw(),
+				start.as_ptr() as *const c_char, start.len(),
+				end.as_ptr() as *const c_char, end.len(),
+			)
+		}
+	}
+}
+
+impl Drop for WriteBatch {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_writebatch_destroy(self.inner) }
+	}
+}
+
+// The database handle, closed once nothing refers to it anymore.
+struct DBInner {
+	raw: *mut ffi::rocksdb_t,
+}
+
+unsafe impl Send for DBInner {}
+unsafe impl Sync for DBInner {}
+
+impl Drop for DBInner {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_close(self.raw) }
+	}
+}
+
+// A column family handle, destroyed before the database it belongs to is closed.
+struct ColumnInner {
+	raw: *mut ffi::rocksdb_column_family_handle_t,
+	_db: Arc<DBInner>,
+}
+
+unsafe impl Send for ColumnInner {}
+unsafe impl Sync for ColumnInner {}
+
+impl Drop for ColumnInner {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_column_family_handle_destroy(self.raw) }
+	}
+}
+
+/// Handle of a column family. Keeps the database open.
+#[derive(Clone)]
+pub struct Column {
+	inner: Arc<ColumnInner>,
+}
+
+impl Column {
+	fn raw(&self) -> *mut ffi::rocksdb_column_family_handle_t {
+		self.inner.raw
+	}
+}
+
+/// An open database. Clones refer to the same database, which is closed when the last clone,
+/// column family handle, snapshot and iterator is dropped.
+#[derive(Clone)]
+pub struct DB {
+	inner: Arc<DBInner>,
+	default: Column,
+}
+
+pub enum Direction {
+	Forward,
+	Reverse,
+}
+
+pub enum IteratorMode<'a> {
+	Start,
+	End,
+	From(&'a [u8], Direction),
+}
+
+impl DB {
+	/// Open the database at `path` with the default column family and the given ones.
+	/// Returns the handles of the given column families, in order.
+	pub fn open(opts: &Options, path: &str, cfnames: &[&str], cf_opts: &[Options]) -> Result<(DB, Vec<Column>), String> {
+		fs::create_dir_all(path).map_err(|e| format!("Failed to create the database directory: {:?}", e))?;
+		Self::open_cfs(opts, path, cfnames, cf_opts, false)
+	}
+
+	/// Open an existing database for reading only. It doesn't see the writes made by
+	/// other processes after it is opened.
+	pub fn open_for_read_only(opts: &Options, path: &str, cfnames: &[&str], cf_opts: &[Options]) -> Result<(DB, Vec<Column>), String> {
+		// RocksDB would create the directory for its log file.
+		if !Path::new(path).exists() {
+			return Err(format!("Database does not exist: {}", path));
+		}
+		Self::open_cfs(opts, path, cfnames, cf_opts, true)
+	}
+
+	// Open the default column family and `cfnames`, returning the handles of `cfnames`.
+	fn open_cfs(
+		opts: &Options,
+		path: &str,
+		cfnames: &[&str],
+		cf_opts: &[Options],
+		read_only: bool,
+	) -> Result<(DB, Vec<Column>), String> {
+		if cfnames.len() != cf_opts.len() {
+			return Err("Mismatching number of column family options".into());
+		}
+		let path = to_cstring(path)?;
+		let names = iter::once("default")
+			.chain(cfnames.iter().cloned())
+			.map(to_cstring)
+			.collect::<Result<Vec<_>, _>>()?;
+		let name_ptrs: Vec<*const c_char> = names.iter().map(|name| name.as_ptr()).collect();
+		let opt_ptrs: Vec<*const ffi::rocksdb_options_t> = iter::once(opts)
+			.chain(cf_opts.iter())
+			.map(|opts| opts.inner as *const _)
+			.collect();
+		let mut handles = vec![ptr::null_mut(); names.len()];
+		let mut err = ptr::null_mut();
+		let raw = unsafe {
+			if read_only {
+				ffi::rocksdb_open_for_read_only_column_families(
+					opts.inner, path.as_ptr(), names.len() as c_int,
+					name_ptrs.as_ptr(), opt_ptrs.as_ptr(), handles.as_mut_ptr(), 0, &mut err,
+				)
+			} else {
+				ffi::rocksdb_open_column_families(
+					opts.inner, path.as_ptr(), names.len() as c_int,
+					name_ptrs.as_ptr(), opt_ptrs.as_ptr(), handles.as_mut_ptr(), &mut err,
+				)
+			}
+		};
+		check_error(err)?;
+		if raw.is_null() || handles.iter().any(|handle| handle.is_null()) {
+			return Err("Could not initialize database".into());
+		}
+		let inner = Arc::new(DBInner { raw });
+		let mut columns: Vec<Column> = handles.into_iter()
+			.map(|raw| Column { inner: Arc::new(ColumnInner { raw, _db: inner.clone() }) })
+			.collect();
+		let default = columns.remove(0);
+		Ok((DB { inner, default }, columns))
+	}
+
+	/// Try to repair a corrupted database.
+	pub fn repair(opts: &Options, path: &str) -> Result<(), String> {
+		let path = to_cstring(path)?;
+		let mut err = ptr::null_mut();
+		unsafe { ffi::rocksdb_repair_db(opts.inner, path.as_ptr(), &mut err) };
+		check_error(err)
+	}
+
+	/// Handle of the default column family.
+	pub fn default_cf(&self) -> &Column {
+		&self.default
+	}
+
+	pub fn create_cf(&self, name: &str, opts: &Options) -> Result<Column, String> {
+		let name = to_cstring(name)?;
+		let mut err = ptr::null_mut();
+		let raw = unsafe { ffi::rocksdb_create_column_family(self.inner.raw, opts.inner, name.as_ptr(), &mut err) };
+		check_error(err)?;
+		Ok(Column { inner: Arc::new(ColumnInner { raw, _db: self.inner.clone() }) })
+	}
+
+	/// Drop a column family. Its data stays readable through the existing handles.
+	pub fn drop_cf(&self, cf: &Column) -> Result<(), String> {
+		let mut err = ptr::null_mut();
+		unsafe { ffi::rocksdb_drop_column_family(self.inner.raw, cf.raw(), &mut err) };
+		check_error(err)
+	}
+
+	pub fn write(&self, batch: &WriteBatch, opts: &WriteOptions) -> Result<(), String> {
+		let mut err = ptr::null_mut();
+		unsafe { ffi::rocksdb_write(self.inner.raw, opts.inner, batch.inner, &mut err) };
+		check_error(err)
+	}
+
+	pub fn get(&self, cf: &Column, key: &[u8], read_opts: &ReadOptions) -> Result<Option<Vec<u8>>, String> {
+		let mut len = 0;
+		let mut err = ptr::null_mut();
+		let value = unsafe {
+			ffi::rocksdb_get_cf(
+				self.inner.raw, read_opts.inner, cf.raw(),
+				key.as_ptr() as *const c_char, key.len(), &mut len, &mut err,
+			)
+		};
+		check_error(err)?;
+		Ok(unsafe { take_value(value, len) })
+	}
+
+	/// Get the values of several keys of a column family in one call.
+	pub fn multi_get(&self, cf: &Column, keys: &[&[u8]], read_opts: &ReadOptions) -> Result<Vec<Option<Vec<u8>>>, String> {
+		let cfs = vec![cf.raw() as *const _; keys.len()];
+		let key_ptrs: Vec<_> = keys.iter().map(|key| key.as_ptr() as *const c_char).collect();
+		let key_lens: Vec<_> = keys.iter().map(|key| key.len()).collect();
+		let mut values = vec![ptr::null_mut(); keys.len()];
+		let mut value_lens = vec![0; keys.len()];
+		let mut errs = vec![ptr::null_mut(); keys.len()];
+		unsafe {
+			ffi::rocksdb_multi_get_cf(
+				self.inner.raw, read_opts.inner, cfs.as_ptr(), keys.len(),
+				key_ptrs.as_ptr(), key_lens.as_ptr(),
+				values.as_mut_ptr(), value_lens.as_mut_ptr(), errs.as_mut_ptr(),
+			)
+		};
+		// every value and error is owned by the caller, so all of them are freed before failing.
+		let values: Vec<_> = values.into_iter().zip(value_lens).map(|(value, len)| unsafe { take_value(value, len) }).collect();
+		let errs: Vec<_> = errs.into_iter().map(check_error).collect();
+		errs.into_iter().collect::<Result<Vec<()>, String>>()?;
+		Ok(values)
+	}
+
+	pub fn iterator(&self, cf: &Column, mode: IteratorMode, read_opts: &ReadOptions) -> DBIterator {
+		let raw = unsafe { ffi::rocksdb_create_iterator_cf(self.inner.raw, read_opts.inner, cf.raw()) };
+		let mut iter = DBIterator {
+			raw,
+			direction: Direction::Forward,
+			just_seeked: true,
+			_cf: cf.clone(),
+		};
+		unsafe {
+			match mode {
+				IteratorMode::Start => ffi::rocksdb_iter_seek_to_first(raw),
+				IteratorMode::End => {
+					ffi::rocksdb_iter_seek_to_last(raw);
+					iter.direction = Direction::Reverse;
+				},
+				// a reverse seek lands on the last key at or before `key`.
+				IteratorMode::From(key, Direction::Forward) =>
+					ffi::rocksdb_iter_seek(raw, key.as_ptr() as *const c_char, key.len()),
+				IteratorMode::From(key, Direction::Reverse) => {
+					ffi::rocksdb_iter_seek_for_prev(raw, key.as_ptr() as *const c_char, key.len());
+					iter.direction = Direction::Reverse;
+				},
+			}
+		}
+		iter
+	}
+
+	/// Get an integer property of a column family, `0` if RocksDB doesn't report it.
+	pub fn property_int(&self, cf: &Column, name: &str) -> u64 {
+		let name = match to_cstring(name) {
+			Ok(name) => name,
+			Err(_) => return 0,
+		};
+		unsafe {
+			let value = ffi::rocksdb_property_value_cf(self.inner.raw, cf.raw(), name.as_ptr());
+			if value.is_null() {
+				return 0;
+			}
+			let parsed = CStr::from_ptr(value).to_str().ok().and_then(|v| v.parse().ok()).unwrap_or(0);
+			ffi::rocksdb_free(value as *mut c_void);
+			parsed
+		}
+	}
+
+	/// Change options of the default column family, like `disable_auto_compactions`.
+	/// RocksDB's C API can't change the options of the other column families.
+	pub fn set_options(&self, options: &[(&str, &str)]) -> Result<(), String> {
+		let keys = options.iter().map(|&(key, _)| to_cstring(key)).collect::<Result<Vec<_>, _>>()?;
+		let values = options.iter().map(|&(_, value)| to_cstring(value)).collect::<Result<Vec<_>, _>>()?;
+		let key_ptrs: Vec<_> = keys.iter().map(|key| key.as_ptr()).collect();
+		let value_ptrs: Vec<_> = values.iter().map(|value| value.as_ptr()).collect();
+		let mut err = ptr::null_mut();
+		unsafe {
+			ffi::rocksdb_set_options(
+				self.inner.raw, options.len() as c_int, key_ptrs.as_ptr(), value_ptrs.as_ptr(), &mut err,
+			)
+		};
+		check_error(err)
+	}
+
+	/// Compact the keys of a column family from `start` up to `end`, or from/to the first/last
+	/// key if not given. Blocks until the compaction is done.
+	pub fn compact_range(&self, cf: &Column, start: Option<&[u8]>, end: Option<&[u8]>) {
+		let (start, start_len) = start.map_or((ptr::null(), 0), |key| (key.as_ptr() as *const c_char, key.len()));
+		let (end, end_len) = end.map_or((ptr::null(), 0), |key| (key.as_ptr() as *const c_char, key.len()));
+		unsafe { ffi::rocksdb_compact_range_cf(self.inner.raw, cf.raw(), start, start_len, end, end_len) }
+	}
+
+	/// Ingest SST files written with `SstFileWriter` into a column family, moving them into
+	/// the database if `move_files` is set. Their keys replace the existing ones.
+	pub fn ingest_external_files(&self, cf: &Column, paths: &[&Path], move_files: bool) -> Result<(), String> {
+		let paths = paths.iter()
+			.map(|path| to_cstring(&path.to_string_lossy()))
+			.collect::<Result<Vec<_>, _>>()?;
+		let path_ptrs: Vec<_> = paths.iter().map(|path| path.as_ptr()).collect();
+		let mut err = ptr::null_mut();
+		unsafe {
+			let opts = ffi::rocksdb_ingestexternalfileoptions_create();
+			ffi::rocksdb_ingestexternalfileoptions_set_move_files(opts, move_files as c_uchar);
+			ffi::rocksdb_ingest_external_file_cf(
+				self.inner.raw, cf.raw(), path_ptrs.as_ptr(), path_ptrs.len(), opts, &mut err,
+			);
+			ffi::rocksdb_ingestexternalfileoptions_destroy(opts);
+		}
+		check_error(err)
+	}
+
+	/// Take a snapshot of the database, which `read_opts` are pinned to.
+	pub fn snapshot(&self, read_opts: ReadOptions) -> Snapshot {
+		let raw = unsafe { ffi::rocksdb_create_snapshot(self.inner.raw) };
+		unsafe { ffi::rocksdb_readoptions_set_snapshot(read_opts.inner, raw) };
+		Snapshot { raw, read_opts, db: self.inner.clone() }
+	}
+
+	/// Create a checkpoint of the database at `path`, which must not exist yet.
+	/// The memtables are flushed first, and the table files are hard-linked if possible.
+	pub fn create_checkpoint(&self, path: &str) -> Result<(), String> {
+		let path = to_cstring(path)?;
+		let mut err = ptr::null_mut();
+		let checkpoint = unsafe { ffi::rocksdb_checkpoint_object_create(self.inner.raw, &mut err) };
+		check_error(err)?;
+		unsafe {
+			ffi::rocksdb_checkpoint_create(checkpoint, path.as_ptr(), 0, &mut err);
+			ffi::rocksdb_checkpoint_object_destroy(checkpoint);
+		}
+		check_error(err)
+	}
+}
+
+// Take ownership of a value allocated by RocksDB, `None` if there is none.
+unsafe fn take_value(value: *mut c_char, len: size_t) -> Option<Vec<u8>> {
+	if value.is_null() {
+		return None;
+	}
+	let owned = slice::from_raw_parts(value as *const u8, len).to_vec();
+	ffi::rocksdb_free(value as *mut c_void);
+	Some(owned)
+}
+
+/// A consistent view of the database. Keeps the database open.
+pub struct Snapshot {
+	raw: *const ffi::rocksdb_snapshot_t,
+	read_opts: ReadOptions,
+	db: Arc<DBInner>,
+}
+
+unsafe impl Send for Snapshot {}
+unsafe impl Sync for Snapshot {}
+
+impl Snapshot {
+	/// Read options reading from the snapshot.
+	pub fn read_options(&self) -> &ReadOptions {
+		&self.read_opts
+	}
+}
+
+impl Drop for Snapshot {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_release_snapshot(self.db.raw, self.raw) }
+	}
+}
+
+/// Iterator over the keys and values of a column family. Keeps the database open.
+pub struct DBIterator {
+	raw: *mut ffi::rocksdb_iterator_t,
+	direction: Direction,
+	just_seeked: bool,
+	_cf: Column,
+}
+
+impl Iterator for DBIterator {
+	type Item = (Box<[u8]>, Box<[u8]>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		unsafe {
+			if !self.just_seeked {
+				match self.direction {
+					Direction::Forward => ffi::rocksdb_iter_next(self.raw),
+					Direction::Reverse => ffi::rocksdb_iter_prev(self.raw),
+				}
+			}
+			self.just_seeked = false;
+			if ffi::rocksdb_iter_valid(self.raw) == 0 {
+				return None;
+			}
+			let (mut key_len, mut value_len) = (0, 0);
+			let key = ffi::rocksdb_iter_key(self.raw, &mut key_len) as *const u8;
+			let value = ffi::rocksdb_iter_value(self.raw, &mut value_len) as *const u8;
+			Some((
+				slice::from_raw_parts(key, key_len).to_vec().into_boxed_slice(),
+				slice::from_raw_parts(value, value_len).to_vec().into_boxed_slice(),
+			))
+		}
+	}
+}
+
+impl Drop for DBIterator {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_iter_destroy(self.raw) }
+	}
+}
+
+/// Writer of SST files, which can be ingested into a database with `DB::ingest_external_files`.
+pub struct SstFileWriter {
+	inner: *mut ffi::rocksdb_sstfilewriter_t,
+	env_opts: *mut ffi::rocksdb_envoptions_t,
+	// the writer refers to the table factory of the options.
+	_opts: Options,
+}
+
+impl SstFileWriter {
+	/// Create a writer of files for a column family with the given options.
+	pub fn create(opts: Options) -> SstFileWriter {
+		unsafe {
+			let env_opts = ffi::rocksdb_envoptions_create();
+			let inner = ffi::rocksdb_sstfilewriter_create(env_opts, opts.inner);
+			SstFileWriter { inner, env_opts, _opts: opts }
+		}
+	}
+
+	pub fn open(&mut self, path: &Path) -> Result<(), String> {
+		let path = to_cstring(&path.to_string_lossy())?;
+		let mut err = ptr::null_mut();
+		unsafe { ffi::rocksdb_sstfilewriter_open(self.inner, path.as_ptr(), &mut err) };
+		check_error(err)
+	}
+
+	/// Add a key to the file. Keys must be added in increasing order.
+	pub fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), String> {
+		let mut err = ptr::null_mut();
+		unsafe {
+			ffi::rocksdb_sstfilewriter_put(
+				self.inner,
+				key.as_ptr() as *const c_char, key.len(),
+				value.as_ptr() as *const c_char, value.len(),
+				&mut err,
+			)
+		};
+		check_error(err)
+	}
+
+	pub fn finish(&mut self) -> Result<(), String> {
+		let mut err = ptr::null_mut();
+		unsafe { ffi::rocksdb_sstfilewriter_finish(self.inner, &mut err) };
+		check_error(err)
+	}
+}
+
+impl Drop for SstFileWriter {
+	fn drop(&mut self) {
+		unsafe {
+			ffi::rocksdb_sstfilewriter_destroy(self.inner);
+			ffi::rocksdb_envoptions_destroy(self.env_opts);
+		}
+	}
+}
+
+/// Incremental backups of databases, stored in a backup directory.
+pub struct BackupEngine {
+	inner: *mut ffi::rocksdb_backup_engine_t,
+}
+
+impl BackupEngine {
+	pub fn open(opts: &Options, path: &str) -> Result<BackupEngine, String> {
+		let path = to_cstring(path)?;
+		let mut err = ptr::null_mut();
+		let inner = unsafe { ffi::rocksdb_backup_engine_open(opts.inner, path.as_ptr(), &mut err) };
+		check_error(err)?;
+		Ok(BackupEngine { inner })
+	}
+
+	/// Back the database up, flushing its memtables first.
+	pub fn create_new_backup(&mut self, db: &DB) -> Result<(), String> {
+		let mut err = ptr::null_mut();
+		unsafe { ffi::rocksdb_backup_engine_create_new_backup(self.inner, db.inner.raw, &mut err) };
+		check_error(err)
+	}
+
+	/// The ids of the backups in the directory, oldest first.
+	pub fn backup_ids(&self) -> Vec<u32> {
+		unsafe {
+			let info = ffi::rocksdb_backup_engine_get_backup_info(self.inner);
+			let ids = (0..ffi::rocksdb_backup_engine_info_count(info))
+				.map(|i| ffi::rocksdb_backup_engine_info_backup_id(info, i))
+				.collect();
+			ffi::rocksdb_backup_engine_info_destroy(info);
+			ids
+		}
+	}
+
+	/// Restore the latest backup to `db_dir`, which must not be used by an open database.
+	pub fn restore_from_latest_backup(&mut self, db_dir: &str) -> Result<(), String> {
+		let db_dir = to_cstring(db_dir)?;
+		let mut err = ptr::null_mut();
+		unsafe {
+			let opts = ffi::rocksdb_restore_options_create();
+			ffi::rocksdb_backup_engine_restore_db_from_latest_backup(
+				self.inner, db_dir.as_ptr(), db_dir.as_ptr(), opts, &mut err,
+			);
+			ffi::rocksdb_restore_options_destroy(opts);
+		}
+		check_error(err)
+	}
+}
+
+impl Drop for BackupEngine {
+	fn drop(&mut self) {
+		unsafe { ffi::rocksdb_backup_engine_close(self.inner) }
+	}
+}
+
+// Callbacks of the merge operators created by `Options::set_merge_operator`.
+mod merge_operator {
+	use std::slice;
+
+	use libc::{c_char, c_int, c_uchar, c_void, size_t};
+
+	const NAME: &[u8] = b"kvdb-rocksdb merge operator\0";
+
+	pub extern "C" fn destroy<F>(state: *mut c_void) {
+		unsafe { drop(Box::from_raw(state as *mut F)) }
+	}
+
+	pub extern "C" fn full_merge<F>(
+		state: *mut c_void,
+		key: *const c_char,
+		key_len: size_t,
+		existing: *const c_char,
+		existing_len: size_t,
+		operands: *const *const c_char,
+		operand_lens: *const size_t,
+		num_operands: c_int,
+		success: *mut c_uchar,
+		new_value_len: *mut size_t,
+	) -> *mut c_char
+		where F: Fn(&[u8], Option<&[u8]>, &[&[u8]]) -> Vec<u8>
+	{
+		unsafe {
+			let merge = &*(state as *const F);
+			let key = slice::from_raw_parts(key as *const u8, key_len);
+			let existing = if existing.is_null() {
+				None
+			} else {
+				Some(slice::from_raw_parts(existing as *const u8, existing_len))
+			};
+			let operands: Vec<&[u8]> = (0..num_operands as usize)
+				.map(|i| slice::from_raw_parts(*operands.add(i) as *const u8, *operand_lens.add(i)))
+				.collect();
+			*success = 1;
+			into_raw(merge(key, existing, &operands).into_boxed_slice(), new_value_len)
+		}
+	}
+
+	// Merge functions need the existing value, they can't combine operands on their own.
+	pub extern "C" fn partial_merge(
+		_state: *mut c_void,
+		_key: *const c_char,
+		_key_len: size_t,
+		_operands: *const *const c_char,
+		_operand_lens: *const size_t,
+		_num_operands: c_int,
+		success: *mut c_uchar,
+		new_value_len: *mut size_t,
+	) -> *mut c_char {
+		unsafe {
+			*success = 0;
+			into_raw(Box::new([]), new_value_len)
+		}
+	}
+
+	// Free a value returned by `full_merge` or `partial_merge`, a boxed slice of `value_len` bytes.
+	pub extern "C" fn delete_value(_state: *mut c_void, value: *const c_char, value_len: size_t) {
+		unsafe { drop(Vec::from_raw_parts(value as *mut u8, value_len, value_len)) }
+	}
+
+	pub extern "C" fn name(_state: *mut c_void) -> *const c_char {
+		NAME.as_ptr() as *const c_char
+	}
+
+	unsafe fn into_raw(value: Box<[u8]>, len: *mut size_t) -> *mut c_char {
+		*len = value.len();
+		Box::into_raw(value) as *mut u8 as *mut c_char
+	}
+}
+
+#[allow(non_camel_case_types)]
+mod ffi {
+	use libc::{c_char, c_int, c_uchar, c_void, size_t};
+
+	pub enum rocksdb_t {}
+	pub enum rocksdb_options_t {}
+	pub enum rocksdb_block_based_table_options_t {}
+	pub enum rocksdb_cache_t {}
+	pub enum rocksdb_readoptions_t {}
+	pub enum rocksdb_writeoptions_t {}
+	pub enum rocksdb_writebatch_t {}
+	pub enum rocksdb_iterator_t {}
+	pub enum rocksdb_snapshot_t {}
+	pub enum rocksdb_column_family_handle_t {}
+	pub enum rocksdb_checkpoint_t {}
+	pub enum rocksdb_backup_engine_t {}
+	pub enum rocksdb_backup_engine_info_t {}
+	pub enum rocksdb_restore_options_t {}
+	pub enum rocksdb_envoptions_t {}
+	pub enum rocksdb_sstfilewriter_t {}
+	pub enum rocksdb_ingestexternalfileoptions_t {}
+	pub enum rocksdb_mergeoperator_t {}
+
+	extern "C" {
+		pub fn rocksdb_free(ptr: *mut c_void);
+
+		pub fn rocksdb_options_create() -> *mut rocksdb_options_t;
+		pub fn rocksdb_options_destroy(opts: *mut rocksdb_options_t);
+		pub fn rocksdb_get_options_from_string(
+			base: *const rocksdb_options_t,
+			opts_str: *const c_char,
+			new_opts: *mut rocksdb_options_t,
+			errptr: *mut *mut c_char,
+		);
+		pub fn rocksdb_options_set_create_if_missing(opts: *mut rocksdb_options_t, v: c_uchar);
+		pub fn rocksdb_options_set_use_fsync(opts: *mut rocksdb_options_t, v: c_int);
+		pub fn rocksdb_options_enable_statistics(opts: *mut rocksdb_options_t);
+		pub fn rocksdb_options_statistics_get_string(opts: *mut rocksdb_options_t) -> *mut c_char;
+		pub fn rocksdb_options_set_max_open_files(opts: *mut rocksdb_options_t, n: c_int);
+		pub fn rocksdb_options_set_db_write_buffer_size(opts: *mut rocksdb_options_t, size: size_t);
+		pub fn rocksdb_options_increase_parallelism(opts: *mut rocksdb_options_t, threads: c_int);
+		pub fn rocksdb_options_optimize_level_style_compaction(opts: *mut rocksdb_options_t, budget: u64);
+		pub fn rocksdb_options_set_target_file_size_base(opts: *mut rocksdb_options_t, size: u64);
+		pub fn rocksdb_options_set_disable_auto_compactions(opts: *mut rocksdb_options_t, disable: c_int);
+		pub fn rocksdb_options_set_block_based_table_factory(
+			opts: *mut rocksdb_options_t,
+			table_opts: *mut rocksdb_block_based_table_options_t,
+		);
+		pub fn rocksdb_options_set_merge_operator(opts: *mut rocksdb_options_t, operator: *mut rocksdb_mergeoperator_t);
+
+		pub fn rocksdb_mergeoperator_create(
+			state: *mut c_void,
+			destructor: extern "C" fn(*mut c_void),
+			full_merge: extern "C" fn(
+				*mut c_void,
+				*const c_char, size_t,
+				*const c_char, size_t,
+				*const *const c_char, *const size_t, c_int,
+				*mut c_uchar, *mut size_t,
+			) -> *mut c_char,
+			partial_merge: extern "C" fn(
+				*mut c_void,
+				*const c_char, size_t,
+				*const *const c_char, *const size_t, c_int,
+				*mut c_uchar, *mut size_t,
+			) -> *mut c_char,
+			delete_value: extern "C" fn(*mut c_void, *const c_char, size_t),
+			name: extern "C" fn(*mut c_void) -> *const c_char,
+		) -> *mut rocksdb_mergeoperator_t;
+
+		pub fn rocksdb_block_based_options_create() -> *mut rocksdb_block_based_table_options_t;
+		pub fn rocksdb_block_based_options_destroy(opts: *mut rocksdb_block_based_table_options_t);
+		pub fn rocksdb_block_based_options_set_block_size(opts: *mut rocksdb_block_based_table_options_t, size: size_t);
+		pub fn rocksdb_block_based_options_set_block_cache(
+			opts: *mut rocksdb_block_based_table_options_t,
+			cache: *mut rocksdb_cache_t,
+		);
+
+		pub fn rocksdb_cache_create_lru(capacity: size_t) -> *mut rocksdb_cache_t;
+		pub fn rocksdb_cache_destroy(cache: *mut rocksdb_cache_t);
+
+		pub fn rocksdb_readoptions_create() -> *mut rocksdb_readoptions_t;
+		pub fn rocksdb_readoptions_destroy(opts: *mut rocksdb_readoptions_t);
+		pub fn rocksdb_readoptions_set_verify_checksums(opts: *mut rocksdb_readoptions_t, v: c_uchar);
+		pub fn rocksdb_readoptions_set_snapshot(opts: *mut rocksdb_readoptions_t, snapshot: *const rocksdb_snapshot_t);
+
+		pub fn rocksdb_writeoptions_create() -> *mut rocksdb_writeoptions_t;
+		pub fn rocksdb_writeoptions_destroy(opts: *mut rocksdb_writeoptions_t);
+		pub fn rocksdb_writeoptions_set_sync(opts: *mut rocksdb_writeoptions_t, v: c_uchar);
+		pub fn rocksdb_writeoptions_disable_WAL(opts: *mut rocksdb_writeoptions_t, disable: c_int);
+
+		pub fn rocksdb_writebatch_create() -> *mut rocksdb_writebatch_t;
+		pub fn rocksdb_writebatch_destroy(batch: *mut rocksdb_writebatch_t);
+		pub fn rocksdb_writebatch_put_cf(
+			batch: *mut rocksdb_writebatch_t,
+			cf: *mut rocksdb_column_family_handle_t,
+			key: *const c_char,
+			klen: size_t,
+			val: *const c_char,
+			vlen: size_t,
+		);
+		pub fn rocksdb_writebatch_delete_cf(
+			batch: *mut rocksdb_writebatch_t,
+			cf: *mut rocksdb_column_family_handle_t,
+			key: *const c_char,
+			klen: size_t,
+		);
+		pub fn rocksdb_writebatch_merge_cf(
+			batch: *mut rocksdb_writebatch_t,
+			cf: *mut rocksdb_column_family_handle_t,
+			key: *const c_char,
+			klen: size_t,
+			val: *const c_char,
+			vlen: size_t,
+		);
+		pub fn rocksdb_writebatch_delete_range_cf(
+			batch: *mut rocksdb_writebatch_t,
+			cf: *mut rocksdb_column_family_handle_t,
+			start_key: *const c_char,
+			start_key_len: size_t,
+			end_key: *const c_char,
+			end_key_len: size_t,
+		);
+
+		pub fn rocksdb_open_column_families(
+			opts: *const rocksdb_options_t,
+			name: *const c_char,
+			num_column_families: c_int,
+			column_family_names: *const *const c_char,
+			column_family_options: *const *const rocksdb_options_t,
+			column_family_handles: *mut *mut rocksdb_column_family_handle_t,
+			errptr: *mut *mut c_char,
+		) -> *mut rocksdb_t;
+		pub fn rocksdb_open_for_read_only_column_families(
+			opts: *const rocksdb_options_t,
+			name: *const c_char,
+			num_column_families: c_int,
+			column_family_names: *const *const c_char,
+			column_family_options: *const *const rocksdb_options_t,
+			column_family_handles: *mut *mut rocksdb_column_family_handle_t,
+			error_if_log_file_exist: c_uchar,
+			errptr: *mut *mut c_char,
+		) -> *mut rocksdb_t;
+		pub fn rocksdb_close(db: *mut rocksdb_t);
+		pub fn rocksdb_set_options(
+			db: *mut rocksdb_t,
+			count: c_int,
+			keys: *const *const c_char,
+			values: *const *const c_char,
+			errptr: *mut *mut c_char,
+		);
+		pub fn rocksdb_compact_range_cf(
+			db: *mut rocksdb_t,
+			cf: *mut rocksdb_column_family_handle_t,
+			start_key: *const c_char,
+			start_key_len: size_t,
+			limit_key: *const c_char,
+			limit_key_len: size_t,
+		);
+		pub fn rocksdb_repair_db(opts: *const rocksdb_options_t, name: *const c_char, errptr: *mut *mut c_char);
+
+		pub fn rocksdb_checkpoint_object_create(db: *mut rocksdb_t, errptr: *mut *mut c_char) -> *mut rocksdb_checkpoint_t;
+		pub fn rocksdb_checkpoint_create(
+			checkpoint: *mut rocksdb_checkpoint_t,
+			checkpoint_dir: *const c_char,
+			log_size_for_flush: u64,
+			errptr: *mut *mut c_char,
+		);
+		pub fn rocksdb_checkpoint_object_destroy(checkpoint: *mut rocksdb_checkpoint_t);
+
+		pub fn rocksdb_backup_engine_open(
+			opts: *const rocksdb_options_t,
+			path: *const c_char,
+			errptr: *mut *mut c_char,
+		) -> *mut rocksdb_backup_engine_t;
+		pub fn rocksdb_backup_engine_create_new_backup(
+			be: *mut rocksdb_backup_engine_t,
+			db: *mut rocksdb_t,
+			errptr: *mut *mut c_char,
+		);
+		pub fn rocksdb_backup_engine_get_backup_info(be: *mut rocksdb_backup_engine_t) -> *const rocksdb_backup_engine_info_t;
+		pub fn rocksdb_backup_engine_info_count(info: *const rocksdb_backup_engine_info_t) -> c_int;
+		pub fn rocksdb_backup_engine_info_backup_id(info: *const rocksdb_backup_engine_info_t, index: c_int) -> u32;
+		pub fn rocksdb_backup_engine_info_destroy(info: *const rocksdb_backup_engine_info_t);
+		pub fn rocksdb_restore_options_create() -> *mut rocksdb_restore_options_t;
+		pub fn rocksdb_restore_options_destroy(opts: *mut rocksdb_restore_options_t);
+		pub fn rocksdb_backup_engine_restore_db_from_latest_backup(
+			be: *mut rocksdb_backup_engine_t,
+			db_dir: *const c_char,
+			wal_dir: *const c_char,
+			restore_options: *const rocksdb_restore_options_t,
+			errptr: *mut *mut c_char,
+		);
+		pub fn rocksdb_backup_engine_close(be: *mut rocksdb_backup_engine_t);
+
+		pub fn rocksdb_envoptions_create() -> *mut rocksdb_envoptions_t;
+		pub fn rocksdb_envoptions_destroy(opts: *mut rocksdb_envoptions_t);
+		pub fn rocksdb_sstfilewriter_create(
+			env: *const rocksdb_envoptions_t,
+			io_options: *const rocksdb_options_t,
+		) -> *mut rocksdb_sstfilewriter_t;
+		pub fn rocksdb_sstfilewriter_open(writer: *mut rocksdb_sstfilewriter_t, name: *const c_char, errptr: *mut *mut c_char);
+		pub fn rocksdb_sstfilewriter_put(
+			writer: *mut rocksdb_sstfilewriter_t,
+			key: *const c_char,
+			keylen: size_t,
+			val: *const c_char,
+			vallen: size_t,
+			errptr: *mut *mut c_char,
+		);
+		pub fn rocksdb_sstfilewriter_finish(writer: *mut rocksdb_sstfilewriter_t, errptr: *mut *mut c_char);
+		pub fn rocksdb_sstfilewriter_destroy(writer: *mut rocksdb_sstfilewriter_t);
+		pub fn rocksdb_ingestexternalfileoptions_create() -> *mut rocksdb_ingestexternalfileoptions_t;
+		pub fn rocksdb_ingestexternalfileoptions_set_move_files(opts: *mut rocksdb_ingestexternalfileoptions_t, move_files: c_uchar);
+		pub fn rocksdb_ingestexternalfileoptions_destroy(opts: *mut rocksdb_ingestexternalfileoptions_t);
+		pub fn rocksdb_ingest_external_file_cf(
+			db: *mut rocksdb_t,
+			handle: *mut rocksdb_column_family_handle_t,
+			file_list: *const *const c_char,
+			list_len: size_t,
+			opts: *const rocksdb_ingestexternalfileoptions_t,
+			errptr: *mut *mut c_char,
+		);
+
+		pub fn rocksdb_create_column_family(
+			db: *mut rocksdb_t,
+			opts: *const rocksdb_options_t,
+			name: *const c_char,
+			errptr: *mut *mut c_char,
+		) -> *mut rocksdb_column_family_handle_t;
+		pub fn rocksdb_drop_column_family(
+			db: *mut rocksdb_t,
+			cf: *mut rocksdb_column_family_handle_t,
+			errptr: *mut *mut c_char,
+		);
+		pub fn rocksdb_column_family_handle_destroy(cf: *mut rocksdb_column_family_handle_t);
+
+		pub fn rocksdb_write(
+			db: *mut rocksdb_t,
+			opts: *const rocksdb_writeoptions_t,
+			batch: *mut rocksdb_writebatch_t,
+			errptr: *mut *mut c_char,
+		);
+		pub fn rocksdb_get_cf(
+			db: *mut rocksdb_t,
+			opts: *const rocksdb_readoptions_t,
+			cf: *mut rocksdb_column_family_handle_t,
+			key: *const c_char,
+			klen: size_t,
+			vlen: *mut size_t,
+			errptr: *mut *mut c_char,
+		) -> *mut c_char;
+		pub fn rocksdb_multi_get_cf(
+			db: *mut rocksdb_t,
+			options: *const rocksdb_readoptions_t,
+			column_families: *const *const rocksdb_column_family_handle_t,
+			num_keys: size_t,
+			keys_list: *const *const c_char,
+			keys_list_sizes: *const size_t,
+			values_list: *mut *mut c_char,
+			values_list_sizes: *mut size_t,
+			errs: *mut *mut c_char,
+		);
+		pub fn rocksdb_property_value_cf(
+			db: *mut rocksdb_t,
+			cf: *mut rocksdb_column_family_handle_t,
+			name: *const c_char,
+		) -> *mut c_char;
+
+		pub fn rocksdb_create_snapshot(db: *mut rocksdb_t) -> *const rocksdb_snapshot_t;
+		pub fn rocksdb_release_snapshot(db: *mut rocksdb_t, snapshot: *const rocksdb_snapshot_t);
+
+		pub fn rocksdb_create_iterator_cf(
+			db: *mut rocksdb_t,
+			opts: *const rocksdb_readoptions_t,
+			cf: *mut rocksdb_column_family_handle_t,
+		) -> *mut rocksdb_iterator_t;
+		pub fn rocksdb_iter_destroy(iter: *mut rocksdb_iterator_t);
+		pub fn rocksdb_iter_valid(iter: *const rocksdb_iterator_t) -> c_uchar;
+		pub fn rocksdb_iter_seek_to_first(iter: *mut rocksdb_iterator_t);
+		pub fn rocksdb_iter_seek_to_last(iter: *mut rocksdb_iterator_t);
+		pub fn rocksdb_iter_seek(iter: *mut rocksdb_iterator_t, key: *const c_char, klen: size_t);
+		pub fn rocksdb_iter_seek_for_prev(iter: *mut rocksdb_iterator_t, key: *const c_char, klen: size_t);
+		pub fn rocksdb_iter_next(iter: *mut rocksdb_iterator_t);
+		pub fn rocksdb_iter_prev(iter: *mut rocksdb_iterator_t);
+		pub fn rocksdb_iter_key(iter: *const rocksdb_iterator_t, klen: *mut size_t) -> *const c_char;
+		pub fn rocksdb_iter_value(iter: *const rocksdb_iterator_t, vlen: *mut size_t) -> *const c_char;
+	}
+}
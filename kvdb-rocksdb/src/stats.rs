@@ -0,0 +1,147 @@
+// Copyright 2015-2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// I/O statistics of a `Database`, accumulated since it was opened.
+///
+/// Take snapshots periodically with `Database::io_stats` and use `since`
+/// to get the activity in between.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct IoStats {
+	/// Number of transactions written.
+	pub transactions: u64,
+	/// Number of keys read.
+	pub reads: u64,
+	/// Number of keys read from the write buffer, without touching RocksDB.
+	pub cache_reads: u64,
+	/// Number of keys written or deleted in RocksDB. A prefix deletion counts as a single write.
+	pub writes: u64,
+	/// Size of the values read, in bytes.
+	pub bytes_read: u64,
+	/// Size of the keys and values written to RocksDB, in bytes. A prefix deletion counts the size of the prefix.
+	pub bytes_written: u64,
+	/// Number of blocks read from the RocksDB block caches.
+	/// `0` unless `DatabaseConfig::enable_statistics` is set, like the other RocksDB statistics.
+	pub block_cache_hits: u64,
+	/// Number of blocks read from disk because they were not in the RocksDB block caches.
+	pub block_cache_misses: u64,
+	/// Time writes were stalled by RocksDB to let compaction catch up.
+	pub stall_time: Duration,
+	/// Memory used by the RocksDB block caches when the statistics were taken, in bytes.
+	pub block_cache_usage: u64,
+	/// Rate RocksDB was slowing writes down to when the statistics were taken, in bytes per second,
+	/// `0` if writes were not stalled by compaction.
+	pub delayed_write_rate: u64,
+	/// Time the statistics were collected over.
+	pub span: Duration,
+}
+
+impl IoStats {
+	/// The change in statistics since `earlier` was taken.
+	/// Values which are not accumulated are the ones of `self`.
+	pub fn since(&self, earlier: &IoStats) -> IoStats {
+		IoStats {
+			transactions: self.transactions.saturating_sub(earlier.transactions),
+			reads: self.reads.saturating_sub(earlier.reads),
+			cache_reads: self.cache_reads.saturating_sub(earlier.cache_reads),
+			writes: self.writes.saturating_sub(earlier.writes),
+			bytes_read: self.bytes_read.saturating_sub(earlier.bytes_read),
+			bytes_written: self.bytes_written.saturating_sub(earlier.bytes_written),
+			block_cache_hits: self.block_cache_hits.saturating_sub(earlier.block_cache_hits),
+			block_cache_misses: self.block_cache_misses.saturating_sub(earlier.block_cache_misses),
+			stall_time: self.stall_time.checked_sub(earlier.stall_time).unwrap_or_default(),
+			block_cache_usage: self.block_cache_usage,
+			delayed_write_rate: self.delayed_write_rate,
+			span: self.span.checked_sub(earlier.span).unwrap_or_default(),
+		}
+	}
+}
+
+// Counters updated by the database while running.
+pub(crate) struct RunningDbStats {
+	started: Instant,
+	transactions: AtomicU64,
+	reads: AtomicU64,
+	cache_reads: AtomicU64,
+	writes: AtomicU64,
+	bytes_read: AtomicU64,
+	bytes_written: AtomicU64,
+}
+
+impl RunningDbStats {
+	pub fn new() -> Self {
+		RunningDbStats {
+			started: Instant::now(),
+			transactions: AtomicU64::new(0),
+			reads: AtomicU64::new(0),
+			cache_reads: AtomicU64::new(0),
+			writes: AtomicU64::new(0),
+			bytes_read: AtomicU64::new(0),
+			bytes_written: AtomicU64::new(0),
+		}
+	}
+
+	pub fn tally_transaction(&self) {
+		self.transactions.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn tally_read(&self, bytes: usize, cached: bool) {
+		self.reads.fetch_add(1, Ordering::Relaxed);
+		if cached {
+			self.cache_reads.fetch_add(1, Ordering::Relaxed);
+		}
+		self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+	}
+
+	pub fn tally_write(&self, bytes: usize) {
+		self.writes.fetch_add(1, Ordering::Relaxed);
+		self.bytes_written.fetch_add(bytes as u64, Ordering::Relaxed);
+	}
+
+	pub fn snapshot(&self) -> IoStats {
+		IoStats {
+			transactions: self.transactions.load(Ordering::Relaxed),
+			reads: self.reads.load(Ordering::Relaxed),
+			cache_reads: self.cache_reads.load(Ordering::Relaxed),
+			writes: self.writes.load(Ordering::Relaxed),
+			bytes_read: self.bytes_read.load(Ordering::Relaxed),
+			bytes_written: self.bytes_written.load(Ordering::Relaxed),
+			span: self.started.elapsed(),
+			..Default::default()
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn io_stats_since() {
+		let earlier = IoStats { reads: 3, bytes_read: 10, block_cache_usage: 8, span: Duration::from_secs(1), ..Default::default() };
+		let later = IoStats { reads: 5, bytes_read: 25, writes: 1, block_cache_usage: 4, span: Duration::from_secs(3), ..Default::default() };
+		assert_eq!(later.since(&earlier), IoStats {
+			reads: 2,
+			bytes_read: 15,
+			writes: 1,
+			block_cache_usage: 4,
+			span: Duration::from_secs(2),
+			..Default::default()
+		});
+	}
+}
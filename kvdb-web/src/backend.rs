@@ -0,0 +1,84 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The IndexedDB operations used by `Database`.
+//!
+//! Columns are stored in object stores numbered `0` for the default column
+//! and `n + 1` for column `n`.
+
+use std::ops::Bound;
+
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use kvdb::{DBTransaction, Direction};
+
+use crate::error::Error;
+
+/// A database opened by a `Backend`.
+pub struct Opened<C> {
+	/// The version of the database.
+	pub version: u32,
+	/// The number of object stores, including the one of the default column.
+	pub columns: u32,
+	/// The connection to the database.
+	pub connection: C,
+}
+
+/// An implementation of the IndexedDB operations, the browser's IndexedDB
+/// or `FakeIndexedDB` (with the `test-helpers` feature) to run outside of browsers.
+pub trait Backend: Clone + Send + Sync + 'static {
+	/// A connection to an open database.
+	type Connection: Send + 'static;
+
+	/// Opens the database with the given name and the specified number of columns
+	/// (not including the default one).
+	///
+	/// Opening with a version higher than the current one upgrades the database, creating
+	/// the object stores of missing columns and deleting the ones of columns beyond `columns`.
+	/// The upgrade closes the other connections to the database once their transactions have
+	/// completed, and fails if it is blocked by connections which aren't closed, e.g. of other pages.
+	/// Without a version, the latest one is opened and missing databases are created.
+	fn open(&self, name: &str, version: Option<u32>, columns: u32)
+		-> BoxFuture<'static, Result<Opened<Self::Connection>, Error>>;
+
+	/// Closes the connection once its transactions have completed.
+	fn close(&self, connection: &Self::Connection);
+
+	/// Commits a transaction to the object stores of the default column and `columns` columns.
+	/// `on_done` is called with the result once the transaction has completed or failed.
+	fn commit(
+		&self,
+		connection: &Self::Connection,
+		transaction: &DBTransaction,
+		columns: u32,
+		on_done: Box<dyn FnOnce(Result<(), Error>)>,
+	);
+
+	/// Reads the value of a key from the object store with the given number.
+	fn get(&self, connection: &Self::Connection, col: u32, key: &[u8])
+		-> BoxFuture<'static, Result<Option<Vec<u8>>, Error>>;
+
+	/// Stream over the keys between `start` and `end` of the object store with the given number,
+	/// in the given direction. The stream ends after an error if the object store can't be read.
+	fn cursor(
+		&self,
+		connection: &Self::Connection,
+		col: u32,
+		start: Bound<&[u8]>,
+		end: Bound<&[u8]>,
+		direction: Direction,
+	) -> BoxStream<'static, Result<(Vec<u8>, Vec<u8>), Error>>;
+}
@@ -27,6 +27,13 @@ pub enum Error {
 	WindowNotAvailable,
 	/// IndexedDB is not supported by your browser.
 	NotSupported(String),
+	/// Opening the IndexedDB has failed.
+	OpenFailed(String),
+	/// Committing a transaction to IndexedDB has failed.
+	/// The in-memory database may contain changes which were not persisted.
+	CommitFailed(String),
+	/// Reading from IndexedDB has failed.
+	ReadFailed(String),
 	/// This enum may grow additional variants,
 	/// so this makes sure clients don't count on exhaustive matching.
 	/// (Otherwise, adding a new variant could break existing code.)
@@ -39,6 +46,9 @@ impl std::error::Error for Error {
 		match *self {
 			Error::WindowNotAvailable => "Accessing a Window has failed",
 			Error::NotSupported(_) => "IndexedDB is not supported by your browser",
+			Error::OpenFailed(_) => "Failed to open the IndexedDB",
+			Error::CommitFailed(_) => "Failed to commit a transaction to IndexedDB",
+			Error::ReadFailed(_) => "Failed to read from IndexedDB",
 			Error::__Nonexhaustive => unreachable!(),
 		}
 	}
@@ -53,7 +63,16 @@ impl fmt::Display for Error {
 				"IndexedDB is not supported by your browser: {}",
 				err,
 			),
+			Error::OpenFailed(ref err) => write!(f, "Failed to open the IndexedDB: {}", err),
+			Error::CommitFailed(ref err) => write!(f, "Failed to commit a transaction to IndexedDB: {}", err),
+			Error::ReadFailed(ref err) => write!(f, "Failed to read from IndexedDB: {}", err),
 			Error::__Nonexhaustive => unreachable!(),
 		}
 	}
 }
+
+impl From<Error> for kvdb::Error {
+	fn from(err: Error) -> kvdb::Error {
+		kvdb::Error::Backend(err.to_string())
+	}
+}
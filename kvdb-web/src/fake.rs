@@ -0,0 +1,206 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An in-process fake of the IndexedDB, to use `Database` outside of browsers.
+
+use std::cmp;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use futures::prelude::*;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use kvdb::{DBOp, DBTransaction, Direction};
+
+use crate::{backend::{Backend, Opened}, error::Error, indexed_db::column_to_number, lock};
+
+/// An in-process fake of the IndexedDB, keeping the databases in memory.
+///
+/// Clones share the databases, so a `Database` can be reopened with a clone
+/// of the backend it was opened with. Transactions complete immediately, and upgrades
+/// close the other connections to the database right away.
+#[derive(Clone, Default)]
+pub struct FakeIndexedDB {
+	inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+	databases: HashMap<String, FakeDatabase>,
+	fail_commits: bool,
+}
+
+struct FakeDatabase {
+	version: u32,
+	// Object stores by number.
+	stores: Vec<BTreeMap<Vec<u8>, Vec<u8>>>,
+	// Whether the connections opened since the last upgrade are closed.
+	connections: Vec<Arc<AtomicBool>>,
+}
+
+/// A connection to a database of a `FakeIndexedDB`.
+pub struct FakeConnection {
+	name: String,
+	closed: Arc<AtomicBool>,
+}
+
+impl FakeIndexedDB {
+	/// Create a fake without databases.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Make the following commits fail, or succeed again.
+	pub fn set_fail_commits(&self, fail: bool) {
+		lock(&self.inner).fail_commits = fail;
+	}
+
+	// Apply all the changes of a transaction, or none of them.
+	fn apply(&self, connection: &FakeConnection, transaction: &DBTransaction, columns: u32) -> Result<(), Error> {
+		let mut inner = lock(&self.inner);
+		if connection.closed.load(Ordering::SeqCst) {
+			return Err(Error::CommitFailed("the connection is closed".into()));
+		}
+		if inner.fail_commits {
+			return Err(Error::CommitFailed("the commits are set to fail".into()));
+		}
+		let db = inner.databases.get_mut(&connection.name).expect("databases are never deleted; qed");
+		if db.stores.len() <= columns as usize {
+			return Err(Error::CommitFailed(format!("the object store col{} does not exist", columns)));
+		}
+		if let Some(col) = transaction.ops.iter().map(|op| column_to_number(op.col())).find(|&col| col > columns) {
+			return Err(Error::CommitFailed(format!("the object store col{} is not in the transaction", col)));
+		}
+		// merges are rejected by `Database` before committing.
+		for op in &transaction.ops {
+			if let DBOp::Merge { col, .. } = *op {
+				return Err(Error::CommitFailed(format!("unsupported merge into col{}", column_to_number(col))));
+			}
+		}
+
+		for op in &transaction.ops {
+			let store = &mut db.stores[column_to_number(op.col()) as usize];
+			match *op {
+				DBOp::Insert { ref key, ref value, .. } => {
+					store.insert(key.to_vec(), value.to_vec());
+				},
+				DBOp::Delete { ref key, .. } => {
+					store.remove(&key[..]);
+				},
+				DBOp::DeletePrefix { ref prefix, .. } => {
+					store.retain(|key, _| !key.starts_with(prefix));
+				},
+				DBOp::Merge { .. } => unreachable!("merges are rejected above; qed"),
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Backend for FakeIndexedDB {
+	type Connection = FakeConnection;
+
+	fn open(&self, name: &str, version: Option<u32>, columns: u32)
+		-> BoxFuture<'static, Result<Opened<Self::Connection>, Error>>
+	{
+		let mut inner = lock(&self.inner);
+		let db = inner.databases.entry(name.to_owned())
+			.or_insert_with(|| FakeDatabase { version: 0, stores: Vec::new(), connections: Vec::new() });
+		// new databases are created with version 1.
+		let version = version.unwrap_or_else(|| cmp::max(db.version, 1));
+		if version < db.version {
+			return future::err(Error::OpenFailed(
+				format!("version {} is lower than the current version {}", version, db.version)
+			)).boxed();
+		}
+		if version > db.version {
+			// upgrade, creating the missing object stores and deleting the ones of dropped columns.
+			db.version = version;
+			db.stores.resize_with(columns as usize + 1, BTreeMap::new);
+			for closed in db.connections.drain(..) {
+				closed.store(true, Ordering::SeqCst);
+			}
+		}
+		db.connections.retain(|closed| !closed.load(Ordering::SeqCst));
+		let closed = Arc::new(AtomicBool::new(false));
+		db.connections.push(closed.clone());
+		future::ok(Opened {
+			version: db.version,
+			columns: db.stores.len() as u32,
+			connection: FakeConnection {
+				name: name.to_owned(),
+				closed,
+			},
+		}).boxed()
+	}
+
+	fn close(&self, connection: &Self::Connection) {
+		connection.closed.store(true, Ordering::SeqCst);
+	}
+
+	fn commit(
+		&self,
+		connection: &Self::Connection,
+		transaction: &DBTransaction,
+		columns: u32,
+		on_done: Box<dyn FnOnce(Result<(), Error>)>,
+	) {
+		let result = self.apply(connection, transaction, columns);
+		on_done(result);
+	}
+
+	fn get(&self, connection: &Self::Connection, col: u32, key: &[u8])
+		-> BoxFuture<'static, Result<Option<Vec<u8>>, Error>>
+	{
+		let inner = lock(&self.inner);
+		let store = inner.databases.get(&connection.name)
+			.and_then(|db| db.stores.get(col as usize))
+			.filter(|_| !connection.closed.load(Ordering::SeqCst));
+		let result = match store {
+			Some(store) => Ok(store.get(key).cloned()),
+			None => Err(Error::ReadFailed(format!("the request for col_{} failed", col))),
+		};
+		future::ready(result).boxed()
+	}
+
+	fn cursor(
+		&self,
+		connection: &Self::Connection,
+		col: u32,
+		start: Bound<&[u8]>,
+		end: Bound<&[u8]>,
+		direction: Direction,
+	) -> BoxStream<'static, Result<(Vec<u8>, Vec<u8>), Error>> {
+		let inner = lock(&self.inner);
+		let store = inner.databases.get(&connection.name)
+			.and_then(|db| db.stores.get(col as usize))
+			.filter(|_| !connection.closed.load(Ordering::SeqCst));
+		let store = match store {
+			Some(store) => store,
+			None => return stream::once(future::err(Error::ReadFailed(format!("the cursor over col_{} failed", col)))).boxed(),
+		};
+		let mut items: Vec<_> = store.iter()
+			.filter(|&(key, _)| kvdb::is_after_start(key, start) && kvdb::is_before_end(key, end))
+			.map(|(key, value)| Ok((key.clone(), value.clone())))
+			.collect();
+		if direction == Direction::Reverse {
+			items.reverse();
+		}
+		stream::iter(items).boxed()
+	}
+}
@@ -19,32 +19,78 @@
 use wasm_bindgen::{JsCast, JsValue, closure::Closure};
 use web_sys::{
 	IdbDatabase, IdbRequest, IdbOpenDbRequest,
-	Event, IdbCursorWithValue,
-	IdbTransactionMode,
+	Event, IdbCursorWithValue, IdbCursorDirection,
+	IdbTransactionMode, IdbKeyRange,
 };
 use js_sys::{Array, Uint8Array, ArrayBuffer};
 
 use futures::channel;
+use futures::future::BoxFuture;
 use futures::prelude::*;
+use futures::stream::BoxStream;
 
-use kvdb::{DBOp, DBTransaction};
+use kvdb::{DBOp, DBTransaction, Direction};
 
-use std::ops::Deref;
+use std::cell::RefCell;
+use std::ops::{Bound, Deref};
+use std::rc::Rc;
 use log::{debug, warn};
+use send_wrapper::SendWrapper;
 
 
-use crate::{Column, error::Error};
+use crate::{Column, backend::{Backend, Opened}, error::Error};
 
-pub struct IndexedDB {
-	pub version: u32,
-	pub columns: u32,
-	pub inner: super::SendWrapper<IdbDatabase>,
+/// The browser's IndexedDB.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IndexedDB;
+
+impl Backend for IndexedDB {
+	type Connection = SendWrapper<IdbDatabase>;
+
+	fn open(&self, name: &str, version: Option<u32>, columns: u32)
+		-> BoxFuture<'static, Result<Opened<Self::Connection>, Error>>
+	{
+		open(name, version, columns).boxed()
+	}
+
+	fn close(&self, connection: &Self::Connection) {
+		connection.close();
+	}
+
+	fn commit(
+		&self,
+		connection: &Self::Connection,
+		transaction: &DBTransaction,
+		columns: u32,
+		on_done: Box<dyn FnOnce(Result<(), Error>)>,
+	) {
+		idb_commit_transaction(connection, transaction, columns, on_done)
+	}
+
+	fn get(&self, connection: &Self::Connection, col: u32, key: &[u8])
+		-> BoxFuture<'static, Result<Option<Vec<u8>>, Error>>
+	{
+		idb_get(connection, col, key).boxed()
+	}
+
+	fn cursor(
+		&self,
+		connection: &Self::Connection,
+		col: u32,
+		start: Bound<&[u8]>,
+		end: Bound<&[u8]>,
+		direction: Direction,
+	) -> BoxStream<'static, Result<(Vec<u8>, Vec<u8>), Error>> {
+		idb_cursor_range(connection, col, start, end, direction).boxed()
+	}
 }
 
 /// Opens the IndexedDB with the given name, version and the specified number of columns
-/// (including the default one).
-pub fn open(name: &str, version: Option<u32>, columns: u32) -> impl Future<Output = Result<IndexedDB, Error>> {
-	let (tx, rx) = channel::oneshot::channel::<IndexedDB>();
+/// (not including the default one).
+fn open(name: &str, version: Option<u32>, columns: u32)
+	-> impl Future<Output = Result<Opened<SendWrapper<IdbDatabase>>, Error>>
+{
+	let (tx, rx) = channel::oneshot::channel();
 
 	let window = match web_sys::window() {
 		Some(window) => window,
@@ -63,7 +109,11 @@ pub fn open(name: &str, version: Option<u32>, columns: u32) -> impl Future<Outpu
 		None => idb_factory.open(name).expect("TypeError is not possible with Rust; qed"),
 	};
 
-	try_create_missing_stores(&open_request, columns, version);
+	upgrade_stores(&open_request, columns, version);
+
+	// called by whichever of the handlers below fires.
+	let tx = Rc::new(RefCell::new(Some(tx)));
+	let tx_error = tx.clone();
 
 	let on_success = Closure::once(move |event: &Event| {
 		// Extract database handle from the event
@@ -80,18 +130,55 @@ pub fn open(name: &str, version: Option<u32>, columns: u32) -> impl Future<Outpu
 		let version = db.version().round() as u32;
 		let columns = db.object_store_names().length();
 
-		// errors if the receiving end was dropped before this call
-		let _ = tx.send(IndexedDB {
-			version,
-			columns,
-			inner: super::SendWrapper::new(db),
+		// let upgrades by other connections proceed once the transactions of this one complete.
+		let connection = db.clone();
+		let on_version_change = Closure::once(move || {
+			debug!("Closing the IndexedDB connection for an upgrade");
+			connection.close();
 		});
+		db.set_onversionchange(Some(on_version_change.as_ref().unchecked_ref()));
+		on_version_change.forget();
+
+		match tx.borrow_mut().take() {
+			// errors if the receiving end was dropped before this call
+			Some(tx) => {
+				let _ = tx.send(Ok(Opened {
+					version,
+					columns,
+					connection: SendWrapper::new(db),
+				}));
+			},
+			// the request has failed as blocked, so nothing uses the connection.
+			None => db.close(),
+		}
 	});
 	open_request.set_onsuccess(Some(on_success.as_ref().unchecked_ref()));
 	on_success.forget();
 
+	// other connections don't close for the upgrade. The request stays pending until they do,
+	// but it's reported as failed rather than waiting indefinitely.
+	let tx_blocked = tx_error.clone();
+	let on_blocked = Closure::once(move || {
+		warn!("Opening the IndexedDB is blocked by other connections");
+		if let Some(tx) = tx_blocked.borrow_mut().take() {
+			let _ = tx.send(Err(Error::OpenFailed(format!("the upgrade to version {:?} is blocked", version))));
+		}
+	});
+	open_request.set_onblocked(Some(on_blocked.as_ref().unchecked_ref()));
+	on_blocked.forget();
+
+	// e.g. the requested version is lower than the current one.
+	let on_error = Closure::once(move || {
+		warn!("Failed to open the IndexedDB");
+		if let Some(tx) = tx_error.borrow_mut().take() {
+			let _ = tx.send(Err(Error::OpenFailed(format!("the request for version {:?} failed", version))));
+		}
+	});
+	open_request.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+	on_error.forget();
+
 	future::Either::Left(
-		rx.then(|r| future::ok(r.expect("Sender isn't dropped; qed")))
+		rx.map(|r| r.expect("One of the handlers is called before the request is dropped; qed"))
 	)
 }
 
@@ -99,7 +186,7 @@ fn store_name(num: u32) -> String {
 	format!("col{}", num)
 }
 
-fn column_to_number(column: Column) -> u32 {
+pub fn column_to_number(column: Column) -> u32 {
 	column.map(|c| c + 1).unwrap_or_default()
 }
 
@@ -116,7 +203,8 @@ fn store_names_js(columns: u32) -> Array {
 	js_array
 }
 
-fn try_create_missing_stores(req: &IdbOpenDbRequest, columns: u32, version: Option<u32>) {
+// Creates the missing object stores and deletes the ones of dropped columns on upgrade.
+fn upgrade_stores(req: &IdbOpenDbRequest, columns: u32, version: Option<u32>) {
 	let on_upgradeneeded = Closure::once(move |event: &Event| {
 		debug!("Upgrading or creating the database to version {:?}, columns {}", version, columns);
 		// Extract database handle from the event
@@ -134,6 +222,13 @@ fn try_create_missing_stores(req: &IdbOpenDbRequest, columns: u32, version: Opti
 				debug!("error creating object store {}: {:?}", name, err);
 			}
 		}
+
+		for name in (columns + 1..previous_columns).map(store_name) {
+			let res = db.delete_object_store(name.as_str());
+			if let Err(err) = res {
+				debug!("error deleting object store {}: {:?}", name, err);
+			}
+		}
 	});
 
 	req.set_onupgradeneeded(Some(on_upgradeneeded.as_ref().unchecked_ref()));
@@ -141,17 +236,28 @@ fn try_create_missing_stores(req: &IdbOpenDbRequest, columns: u32, version: Opti
 }
 
 /// Commit a transaction to the IndexedDB.
-pub fn idb_commit_transaction(
+/// `on_done` is called with the result once the transaction has completed or failed.
+fn idb_commit_transaction<F>(
 	idb: &IdbDatabase,
 	txn: &DBTransaction,
 	columns: u32,
-) -> impl Future<Output = ()> {
+	on_done: F,
+) where F: FnOnce(Result<(), Error>) + 'static {
+	// merges are rejected by `Database` before committing.
+	for op in &txn.ops {
+		if let DBOp::Merge { col, .. } = *op {
+			return on_done(Err(Error::CommitFailed(format!("unsupported merge into col_{}", column_to_number(col)))));
+		}
+	}
+
 	let store_names_js = store_names_js(columns);
 
 	// Create a transaction
 	let mode = IdbTransactionMode::Readwrite;
-	let idb_txn = idb.transaction_with_str_sequence_and_mode(&store_names_js, mode)
-		.expect("The provided mode and store names are valid; qed");
+	let idb_txn = match idb.transaction_with_str_sequence_and_mode(&store_names_js, mode) {
+		Ok(idb_txn) => idb_txn,
+		Err(err) => return on_done(Err(Error::CommitFailed(format!("{:?}", err)))),
+	};
 
 	// Open object stores (columns)
 	let object_stores = (0..=columns).map(|n| {
@@ -159,6 +265,8 @@ pub fn idb_commit_transaction(
 			.expect("Object stores were created in try_create_object_stores; qed")
 	}).collect::<Vec<_>>();
 
+	// the first operation which could not be added to the transaction, if any.
+	let mut failure = None;
 	for op in &txn.ops {
 		match op {
 			DBOp::Insert { col, key, value } => {
@@ -172,6 +280,7 @@ pub fn idb_commit_transaction(
 				let res = object_stores[column].put_with_key(val_js.as_ref(), key_js.as_ref());
 				if let Err(err) = res {
 					warn!("error inserting key/values into col_{}: {:?}", column, err);
+					failure.get_or_insert(err);
 				}
 			},
 			DBOp::Delete { col, key } => {
@@ -184,42 +293,182 @@ pub fn idb_commit_transaction(
 				let res = object_stores[column].delete(key_js.as_ref());
 				if let Err(err) = res {
 					warn!("error deleting key from col_{}: {:?}", column, err);
+					failure.get_or_insert(err);
+				}
+			},
+			DBOp::DeletePrefix { col, prefix } => {
+				let column = column_to_number(*col) as usize;
+
+				// Delete all keys in the range [prefix, end_prefix)
+				let res = if prefix.is_empty() {
+					object_stores[column].clear()
+				} else {
+					let lower_js = Uint8Array::from(prefix.as_ref());
+					let range = match kvdb::end_prefix(prefix) {
+						Some(end) => {
+							let upper_js = Uint8Array::from(end.as_ref());
+							IdbKeyRange::bound_with_lower_open_and_upper_open(&lower_js, &upper_js, false, true)
+						},
+						None => IdbKeyRange::lower_bound(&lower_js),
+					}.expect("The lower bound is less than the upper bound; qed");
+					object_stores[column].delete(range.as_ref())
+				};
+				if let Err(err) = res {
+					warn!("error deleting prefix from col_{}: {:?}", column, err);
+					failure.get_or_insert(err);
 				}
 			},
+			DBOp::Merge { .. } => unreachable!("merges are rejected above; qed"),
 		}
 	}
 
-	let (tx, rx) = channel::oneshot::channel::<()>();
+	// don't commit a part of the transaction.
+	if let Some(err) = failure {
+		let _ = idb_txn.abort();
+		return on_done(Err(Error::CommitFailed(format!("{:?}", err))));
+	}
 
-	let on_complete = Closure::once(move || {
-		let _ = tx.send(());
-	});
+	// called by whichever of the handlers below fires first.
+	let on_done = Rc::new(RefCell::new(Some(on_done)));
+	let done = move |on_done: Rc<RefCell<Option<F>>>, result| {
+		if let Some(on_done) = on_done.borrow_mut().take() {
+			on_done(result);
+		}
+	};
+
+	let on_done_clone = on_done.clone();
+	let on_complete = Closure::once(move || done(on_done_clone, Ok(())));
 	idb_txn.set_oncomplete(Some(on_complete.as_ref().unchecked_ref()));
 	on_complete.forget();
 
-	let on_error = Closure::once(move || {
+	// failed requests abort the transaction, which fires `onerror` for each of them and then `onabort`.
+	let on_done_clone = on_done.clone();
+	let on_error = Closure::wrap(Box::new(move || {
 		warn!("Failed to commit a transaction to IndexedDB");
-	});
+		done(on_done_clone.clone(), Err(Error::CommitFailed("a request of the transaction failed".into())));
+	}) as Box<dyn FnMut()>);
 	idb_txn.set_onerror(Some(on_error.as_ref().unchecked_ref()));
 	on_error.forget();
 
-	rx.map(|_| ())
+	let on_abort = Closure::once(move || {
+		done(on_done, Err(Error::CommitFailed("the transaction was aborted".into())));
+	});
+	idb_txn.set_onabort(Some(on_abort.as_ref().unchecked_ref()));
+	on_abort.forget();
 }
 
 
-/// Returns a cursor to a database column with the given column number.
-pub fn idb_cursor(idb: &IdbDatabase, col: u32) -> impl Stream<Item = (Vec<u8>, Vec<u8>)> {
-	// TODO: we could read all the columns in one db transaction
+/// Reads the value of a key from a database column with the given column number.
+fn idb_get(idb: &IdbDatabase, col: u32, key: &[u8]) -> impl Future<Output = Result<Option<Vec<u8>>, Error>> {
+	let (tx, rx) = channel::oneshot::channel::<Result<Option<Vec<u8>>, Error>>();
+
 	let store_name = store_name(col);
 	let store_name = store_name.as_str();
-	let txn = idb.transaction_with_str(store_name)
-		.expect("The stores were created on open: {}; qed");
+	// fails for the object stores of dropped columns.
+	let store = match idb.transaction_with_str(store_name).and_then(|txn| txn.object_store(store_name)) {
+		Ok(store) => store,
+		Err(err) => return future::Either::Right(future::err(Error::ReadFailed(format!("{:?}", err)))),
+	};
+
+	let req = match store.get(Uint8Array::from(key).as_ref()) {
+		Ok(req) => req,
+		Err(err) => return future::Either::Right(future::err(Error::ReadFailed(format!("{:?}", err)))),
+	};
+
+	// called by whichever of the handlers below fires.
+	let tx = Rc::new(RefCell::new(Some(tx)));
+	let tx_error = tx.clone();
+
+	let on_success = Closure::once(move |event: &Event| {
+		// Extract the value from the event, `undefined` if there is none
+		let target = event.target().expect("Event should have a target; qed");
+		let req = target.dyn_ref::<IdbRequest>().expect("Event target is IdbRequest; qed");
+		let result = req.result().expect("IdbRequest should have a result; qed");
 
-	let store = txn.object_store(store_name).expect("Opening a store shouldn't fail; qed");
-	let cursor = store.open_cursor().expect("Opening a cursor shouldn't fail; qed");
+		let value = if result.is_undefined() {
+			None
+		} else {
+			// Copy the js array into a rust `Vec`
+			let v: &Uint8Array = result.unchecked_ref();
+			let mut vv = vec![0u8; v.byte_length() as usize];
+			v.copy_to(&mut vv[..]);
+			Some(vv)
+		};
+		if let Some(tx) = tx.borrow_mut().take() {
+			let _ = tx.send(Ok(value));
+		}
+	});
+	req.set_onsuccess(Some(on_success.as_ref().unchecked_ref()));
+	on_success.forget();
+
+	let on_error = Closure::once(move || {
+		warn!("Failed to read a value from IndexedDB");
+		if let Some(tx) = tx_error.borrow_mut().take() {
+			let _ = tx.send(Err(Error::ReadFailed(format!("the request for col_{} failed", col))));
+		}
+	});
+	req.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+	on_error.forget();
 
+	future::Either::Left(
+		rx.map(|r| r.expect("One of the handlers is called before the request is dropped; qed"))
+	)
+}
+
+// Key range between the bounds, `undefined` for all the keys. Fails for empty ranges.
+fn key_range(start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<JsValue, JsValue> {
+	let bound = |bound: Bound<&[u8]>| match bound {
+		Bound::Included(key) => Some((Uint8Array::from(key), false)),
+		Bound::Excluded(key) => Some((Uint8Array::from(key), true)),
+		Bound::Unbounded => None,
+	};
+	let range = match (bound(start), bound(end)) {
+		(None, None) => return Ok(JsValue::UNDEFINED),
+		(Some((lower, open)), None) => IdbKeyRange::lower_bound_with_open(&lower, open)?,
+		(None, Some((upper, open))) => IdbKeyRange::upper_bound_with_open(&upper, open)?,
+		(Some((lower, lower_open)), Some((upper, upper_open))) =>
+			IdbKeyRange::bound_with_lower_open_and_upper_open(&lower, &upper, lower_open, upper_open)?,
+	};
+	Ok(range.into())
+}
+
+/// Returns a cursor over the keys between `start` and `end` of a database column
+/// with the given column number, in the given direction.
+fn idb_cursor_range(
+	idb: &IdbDatabase,
+	col: u32,
+	start: Bound<&[u8]>,
+	end: Bound<&[u8]>,
+	direction: Direction,
+) -> impl Stream<Item = Result<(Vec<u8>, Vec<u8>), Error>> {
 	let (tx, rx) = channel::mpsc::unbounded();
 
+	let range = match key_range(start, end) {
+		Ok(range) => range,
+		// the range is empty, so is the stream.
+		Err(_) => return rx,
+	};
+	let direction = match direction {
+		Direction::Forward => IdbCursorDirection::Next,
+		Direction::Reverse => IdbCursorDirection::Prev,
+	};
+
+	// TODO: we could read all the columns in one db transaction
+	let store_name = store_name(col);
+	let store_name = store_name.as_str();
+	// fails for the object stores of dropped columns, ending the stream after the error.
+	let cursor = idb.transaction_with_str(store_name)
+		.and_then(|txn| txn.object_store(store_name))
+		.and_then(|store| store.open_cursor_with_range_and_direction(&range, direction));
+	let cursor = match cursor {
+		Ok(cursor) => cursor,
+		Err(err) => {
+			let _ = tx.unbounded_send(Err(Error::ReadFailed(format!("{:?}", err))));
+			return rx;
+		},
+	};
+
+	let tx_error = tx.clone();
 	let on_cursor = Closure::wrap(Box::new(move |event: &Event| {
 		// Extract the cursor from the event
 		let target = event.target().expect("on_cursor should have a target; qed");
@@ -237,7 +486,7 @@ pub fn idb_cursor(idb: &IdbDatabase, col: u32) -> impl Stream<Item = (Vec<u8>, V
 			Uint8Array::new(k).copy_to(&mut kv[..]);
 			v.copy_to(&mut vv[..]);
 
-			if let Err(e) = tx.unbounded_send((kv, vv)) {
+			if let Err(e) = tx.unbounded_send(Ok((kv, vv))) {
 				warn!("on_cursor: error sending to a channel {:?}", e);
 			}
 			if let Err(e) = cursor.deref().continue_() {
@@ -252,5 +501,14 @@ pub fn idb_cursor(idb: &IdbDatabase, col: u32) -> impl Stream<Item = (Vec<u8>, V
 	cursor.set_onsuccess(Some(on_cursor.as_ref().unchecked_ref()));
 	on_cursor.forget();
 
+	// e.g. the transaction is aborted while iterating, ending the stream after the error.
+	let on_error = Closure::once(move || {
+		warn!("Failed to iterate over IndexedDB");
+		let _ = tx_error.unbounded_send(Err(Error::ReadFailed(format!("the cursor over col_{} failed", col))));
+		tx_error.close_channel();
+	});
+	cursor.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+	on_error.forget();
+
 	rx
 }
@@ -18,33 +18,160 @@
 //!
 //! Writes data both into memory and IndexedDB, reads the whole database in memory
 //! from the IndexedDB on `open`.
+//!
+//! Changes are committed to IndexedDB in the background. Use `Database::flush_async`
+//! to wait for them to complete.
+//!
+//! Databases opened with `Database::open_lazy` only keep recently used values in memory
+//! and read the rest from the IndexedDB through the `AsyncKeyValueDB` interface. Their
+//! `KeyValueDB::get` fails with `kvdb::Error::Unsupported` for values which are not cached.
+//!
+//! The IndexedDB operations are implemented by a `Backend`. With the `test-helpers` feature,
+//! databases opened with `FakeIndexedDB` are kept in memory, so they can be used outside of
+//! browsers, e.g. in tests.
 
 #![deny(missing_docs)]
 
+mod backend;
 mod error;
+#[cfg(feature = "test-helpers")]
+mod fake;
 mod indexed_db;
 
-use std::io;
-use std::rc::Rc;
-use std::sync::Mutex;
-use kvdb::{DBValue, DBTransaction};
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::{AtomicU32, Ordering};
+use kvdb::{DBValue, DBTransaction, DBOp, Direction, KeyValueDBSnapshot, ColumnStats};
+use kvdb::migration::ColumnManager;
+use futures::channel::oneshot;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
 use kvdb_memorydb::{InMemory, self as in_memory};
-use send_wrapper::SendWrapper;
+use lru::LruCache;
 
+pub use backend::{Backend, Opened};
 pub use error::Error;
-pub use kvdb::KeyValueDB;
+#[cfg(feature = "test-helpers")]
+pub use fake::{FakeConnection, FakeIndexedDB};
+pub use indexed_db::IndexedDB;
+pub use kvdb::{AsyncKeyValueDB, KeyValueDB};
 
 use futures::prelude::*;
 
-use web_sys::IdbDatabase;
-
 /// Database backed by both IndexedDB and in memory implementation.
-pub struct Database {
+pub struct Database<B: Backend = IndexedDB> {
 	name: String,
-	version: u32,
-	columns: u32,
-	in_memory: InMemory,
-	indexed_db: Mutex<SendWrapper<IdbDatabase>>,
+	version: AtomicU32,
+	columns: AtomicU32,
+	storage: Storage,
+	backend: B,
+	indexed_db: Mutex<B::Connection>,
+	pending: Arc<Mutex<PendingCommits>>,
+}
+
+// Values kept in memory.
+enum Storage {
+	// The whole database.
+	Mirror(InMemory),
+	// Recently used values. The rest is read from the IndexedDB.
+	Lazy(Mutex<Cache>),
+}
+
+// Recently used values of a lazily opened database.
+struct Cache {
+	// Cached values by column and key, `None` for missing keys.
+	values: LruCache<(Column, Vec<u8>), Option<DBValue>>,
+	// Number of transactions written so far.
+	generation: u64,
+	// Generation of the last write of the keys and of the deleted prefixes written while values
+	// are fetched from the IndexedDB, which may have been read before these writes.
+	// Cleared once no fetch is in progress.
+	written: HashMap<(Column, Vec<u8>), u64>,
+	deleted_prefixes: Vec<(Column, Vec<u8>, u64)>,
+	// Number of fetches in progress.
+	fetching: usize,
+}
+
+impl Cache {
+	fn new(size: usize) -> Self {
+		Cache {
+			values: LruCache::new(size),
+			generation: 0,
+			written: HashMap::new(),
+			deleted_prefixes: Vec::new(),
+			fetching: 0,
+		}
+	}
+
+	// Apply the changes of a transaction to the cached values.
+	fn write(&mut self, transaction: &DBTransaction) {
+		self.generation += 1;
+		let generation = self.generation;
+		for op in &transaction.ops {
+			match *op {
+				DBOp::Insert { col, ref key, ref value } => self.put(col, key, Some(value.clone()), generation),
+				DBOp::Delete { col, ref key } => self.put(col, key, None, generation),
+				DBOp::DeletePrefix { col, ref prefix } => {
+					let deleted: Vec<_> = self.values.iter()
+						.filter(|&(&(c, ref key), _)| c == col && key.starts_with(prefix))
+						.map(|(key, _)| key.clone())
+						.collect();
+					for key in deleted {
+						self.values.put(key, None);
+					}
+					if self.fetching > 0 {
+						self.deleted_prefixes.push((col, prefix.to_vec(), generation));
+					}
+				},
+				DBOp::Merge { .. } => unreachable!("merges are rejected before writing; qed"),
+			}
+		}
+	}
+
+	fn put(&mut self, col: Column, key: &[u8], value: Option<DBValue>, generation: u64) {
+		if self.fetching > 0 {
+			self.written.insert((col, key.to_vec()), generation);
+		}
+		self.values.put((col, key.to_vec()), value);
+	}
+
+	// Whether a key was written after the given generation.
+	fn written_since(&self, col: Column, key: &[u8], generation: u64) -> bool {
+		self.written.get(&(col, key.to_vec())).map_or(false, |&written| written > generation)
+			|| self.deleted_prefixes.iter()
+				.any(|&(c, ref prefix, written)| c == col && key.starts_with(prefix) && written > generation)
+	}
+}
+
+// Keeps track of a fetch from the IndexedDB, counted in `Cache::fetching`, until it is dropped.
+struct Fetching<'a>(&'a Mutex<Cache>);
+
+impl<'a> Drop for Fetching<'a> {
+	fn drop(&mut self) {
+		let mut cache = lock(self.0);
+		cache.fetching -= 1;
+		if cache.fetching == 0 {
+			cache.written.clear();
+			cache.deleted_prefixes.clear();
+		}
+	}
+}
+
+// Transactions being committed to the IndexedDB.
+#[derive(Default)]
+struct PendingCommits {
+	count: usize,
+	// First failure since the last flush.
+	failure: Option<Error>,
+	// Flushes waiting for the commits to complete.
+	flushes: Vec<oneshot::Sender<Result<(), Error>>>,
+}
+
+// Nothing panics while holding the locks, so poisoning is ignored.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+	mutex.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 // The default column is represented as `None`.
@@ -59,60 +186,67 @@ impl Database {
 	/// Opens the database with the given name,
 	/// and the specified number of columns (not including the default one).
 	pub fn open(name: String, columns: u32) -> impl Future<Output = Result<Database, error::Error>> {
-		// let's try to open the latest version of the db first
-		let open_request = indexed_db::open(name.as_str(), None, columns);
-		let name_clone = name.clone();
-		open_request.then(move |db| {
-			let db = match db {
-				Ok(db) => db,
-				Err(err) => return future::Either::Right(future::err(err)),
-			};
+		Database::open_with(IndexedDB, name, columns)
+	}
 
-			// If we need more column than the latest version has,
-			// then bump the version (+ 1 for the default column).
-			// In order to bump the version, we close the database
-			// and reopen it with a higher version than it was opened with previously.
-			// cf. https://github.com/paritytech/parity-common/pull/202#discussion_r321221751
-			if columns + 1 > db.columns {
-				let next_version = db.version + 1;
-				drop(db);
-				future::Either::Left(indexed_db::open(name.as_str(), Some(next_version), columns).boxed())
-			} else {
-				future::Either::Left(future::ok(db).boxed())
-			}
-		// populate the in_memory db from the IndexedDB
-		}).then(move |db| {
-			let db = match db {
-				Ok(db) => db,
-				Err(err) => return future::Either::Right(future::err(err)),
-			};
+	/// Opens the database with the given name and number of columns without reading it,
+	/// keeping up to `cache_size` recently used values in memory.
+	///
+	/// Values which are not cached are only available through the `AsyncKeyValueDB` interface,
+	/// `KeyValueDB::get` and `get_many` fail with `kvdb::Error::Unsupported` for them, even if
+	/// they exist. Iteration is only available through
+	/// `AsyncKeyValueDB::iter` and `iter_range_async`, and snapshots are not supported.
+	pub fn open_lazy(name: String, columns: u32, cache_size: usize)
+		-> impl Future<Output = Result<Database, error::Error>>
+	{
+		Database::open_lazy_with(IndexedDB, name, columns, cache_size)
+	}
+}
 
-			let indexed_db::IndexedDB { version, inner, .. } = db;
-			let rc = Rc::new(inner.take());
-			let weak = Rc::downgrade(&rc);
+impl<B: Backend> Database<B> {
+	/// Opens the database with the given backend, see `Database::open`.
+	pub fn open_with(backend: B, name: String, columns: u32) -> impl Future<Output = Result<Self, error::Error>> {
+		open_latest(backend.clone(), name.clone(), columns)
+		// populate the in_memory db from the IndexedDB
+		.and_then(move |db| {
+			let Opened { version, connection, .. } = db;
 			// read the columns from the IndexedDB
-			future::Either::Left(stream::iter(0..=columns).map(move |n| {
-				let db = weak.upgrade().expect("rc should live at least as long; qed");
-				indexed_db::idb_cursor(&db, n).fold(DBTransaction::new(), move |mut txn, (key, value)| {
-					let column = number_to_column(n);
-					txn.put_vec(column, key.as_ref(), value);
-					future::ready(txn)
-				})
+			let columns_read: Vec<_> = (0..=columns).map(|n| {
+				let column = number_to_column(n);
+				backend.cursor(&connection, n, Bound::Unbounded, Bound::Unbounded, Direction::Forward)
+					.try_fold(DBTransaction::new(), move |mut txn, (key, value)| {
+						txn.put_vec(column, key.as_ref(), value);
+						future::ok(txn)
+					})
+			}).collect();
 			// write each column into memory
-			}).fold(in_memory::create(columns), |m, txn| {
-				txn.then(|txn| {
-					m.write_buffered(txn);
-					future::ready(m)
-				})
-			}).then(move |in_memory| future::ok(Database {
-				name: name_clone,
-				version,
-				columns,
-				in_memory,
-				indexed_db: Mutex::new(SendWrapper::new(
-					Rc::try_unwrap(rc).expect("should have only 1 ref at this point; qed")
-				)),
-			})))
+			stream::iter(columns_read).then(|txn| txn).try_fold(in_memory::create(columns), |m, txn| {
+				m.write_buffered(txn);
+				future::ok(m)
+			}).map_ok(move |in_memory| Database {
+				name,
+				version: AtomicU32::new(version),
+				columns: AtomicU32::new(columns),
+				storage: Storage::Mirror(in_memory),
+				backend,
+				indexed_db: Mutex::new(connection),
+				pending: Arc::new(Mutex::new(PendingCommits::default())),
+			})
+		})
+	}
+
+	/// Opens the database with the given backend without reading it, see `Database::open_lazy`.
+	pub fn open_lazy_with(backend: B, name: String, columns: u32, cache_size: usize)
+		-> impl Future<Output = Result<Self, error::Error>>
+	{
+		open_latest(backend.clone(), name.clone(), columns).map_ok(move |db| Database {
+			name,
+			version: AtomicU32::new(db.version),
+			columns: AtomicU32::new(columns),
+			storage: Storage::Lazy(Mutex::new(Cache::new(cache_size))),
+			backend,
+			indexed_db: Mutex::new(db.connection),
+			pending: Arc::new(Mutex::new(PendingCommits::default())),
 		})
 	}
 
@@ -123,52 +257,338 @@ impl Database {
 
 	/// Get the database version.
 	pub fn version(&self) -> u32 {
-		self.version
+		self.version.load(Ordering::SeqCst)
+	}
+
+	/// Get the number of columns (not including the default one).
+	pub fn num_columns(&self) -> u32 {
+		self.columns.load(Ordering::SeqCst)
+	}
+
+	/// Add a column, bumping the database version.
+	///
+	/// The IndexedDB is reopened, so no changes may be written until the returned future resolves.
+	pub fn add_column(&self) -> impl Future<Output = Result<(), Error>> + '_ {
+		let column = self.num_columns();
+		self.set_columns(column + 1).and_then(move |()| {
+			// the IndexedDB may have been opened with less columns than it has.
+			let mut clear = DBTransaction::new();
+			clear.delete_prefix(column, &[]);
+			let (tx, rx) = oneshot::channel();
+			self.commit(&clear, move |result| {
+				let _ = tx.send(result);
+			});
+			rx.map(|result| result.unwrap_or_else(|_| Err(Error::CommitFailed("the commit was dropped".into()))))
+		})
+	}
+
+	/// Drop the last column, bumping the database version.
+	///
+	/// The IndexedDB is reopened, so no changes may be written until the returned future resolves.
+	pub fn drop_column(&self) -> impl Future<Output = Result<(), Error>> + '_ {
+		match self.num_columns() {
+			0 => future::Either::Left(future::ok(())),
+			columns => future::Either::Right(self.set_columns(columns - 1)),
+		}
+	}
+
+	// Reopen the IndexedDB with a higher version, creating or deleting object stores
+	// to match the number of columns.
+	fn set_columns(&self, columns: u32) -> impl Future<Output = Result<(), Error>> + '_ {
+		// the current connection is used until the upgrade closes it, once its transactions complete.
+		let next_version = self.version() + 1;
+		self.backend.open(self.name.as_str(), Some(next_version), columns).map_ok(move |db| {
+			let previous = mem::replace(&mut *lock(&self.indexed_db), db.connection);
+			self.backend.close(&previous);
+			self.version.store(db.version, Ordering::SeqCst);
+			let previous = self.columns.swap(columns, Ordering::SeqCst);
+			match self.storage {
+				Storage::Mirror(ref in_memory) => {
+					for _ in columns..previous {
+						let _ = in_memory.drop_column();
+					}
+					for _ in previous..columns {
+						let _ = in_memory.add_column();
+					}
+				},
+				Storage::Lazy(ref cache) => {
+					let cache = &mut lock(cache).values;
+					let dropped: Vec<_> = cache.iter()
+						.filter(|&(&(col, _), _)| col.map_or(false, |c| c >= columns))
+						.map(|(key, _)| key.clone())
+						.collect();
+					for key in dropped {
+						cache.pop(&key);
+					}
+				},
+			}
+		})
+	}
+
+	// The in-memory database, unless it is opened lazily.
+	fn in_memory(&self) -> kvdb::Result<&InMemory> {
+		match self.storage {
+			Storage::Mirror(ref in_memory) => Ok(in_memory),
+			Storage::Lazy(_) => Err(kvdb::Error::Unsupported("Not supported by lazily opened databases")),
+		}
+	}
+
+	fn check_column(&self, col: Column) -> kvdb::Result<()> {
+		match col {
+			Some(c) if c >= self.num_columns() => Err(kvdb::Error::UnknownColumn(col)),
+			_ => Ok(()),
+		}
+	}
+
+	// Write a transaction to memory. Transactions with merges are rejected, since there are
+	// no merge functions and lazily opened databases can't read the values to merge into.
+	fn write_in_memory(&self, transaction: &DBTransaction) -> Result<(), Error> {
+		for op in &transaction.ops {
+			if let DBOp::Merge { col, .. } = *op {
+				return Err(Error::CommitFailed(format!("unsupported merge into col_{}", indexed_db::column_to_number(col))));
+			}
+		}
+		match self.storage {
+			Storage::Mirror(ref in_memory) => in_memory.write_buffered(transaction.clone()),
+			Storage::Lazy(ref cache) => lock(cache).write(transaction),
+		}
+		Ok(())
+	}
+
+	// Commit a transaction to the IndexedDB, keeping track of it until it completes.
+	fn commit<F>(&self, transaction: &DBTransaction, on_commit: F) where F: FnOnce(Result<(), Error>) + 'static {
+		lock(&self.pending).count += 1;
+		let pending = self.pending.clone();
+		let on_done = move |result: Result<(), Error>| {
+			{
+				let mut pending = lock(&pending);
+				pending.count -= 1;
+				if let Err(ref err) = result {
+					pending.failure.get_or_insert_with(|| err.clone());
+				}
+				if pending.count == 0 && !pending.flushes.is_empty() {
+					let flushed = pending.failure.take().map_or(Ok(()), Err);
+					for flush in pending.flushes.drain(..) {
+						let _ = flush.send(flushed.clone());
+					}
+				}
+			}
+			on_commit(result);
+		};
+		match self.indexed_db.lock() {
+			Ok(guard) => self.backend.commit(&guard, transaction, self.num_columns(), Box::new(on_done)),
+			Err(_) => on_done(Err(Error::CommitFailed("IndexedDB handle is poisoned".into()))),
+		}
+	}
+
+	/// Stream over the keys of a column between `start` and `end`, in the given direction.
+	/// Lazily opened databases read them from the IndexedDB.
+	pub fn iter_range_async<'a>(
+		&'a self,
+		col: Option<u32>,
+		start: Bound<&'a [u8]>,
+		end: Bound<&'a [u8]>,
+		direction: Direction,
+	) -> BoxStream<'a, kvdb::Result<(Box<[u8]>, Box<[u8]>)>> {
+		if let Storage::Mirror(ref in_memory) = self.storage {
+			// NOTE: clones the whole range
+			let items = match in_memory.iter_range(col, start, end, direction) {
+				Ok(iter) => iter.map(Ok).collect(),
+				Err(err) => vec![Err(err)],
+			};
+			return stream::iter(items).boxed();
+		}
+		if let Err(err) = self.check_column(col) {
+			return stream::once(future::err(err)).boxed();
+		}
+		match self.indexed_db.lock() {
+			Ok(guard) => self.backend.cursor(&guard, indexed_db::column_to_number(col), start, end, direction)
+				.map_ok(|(key, value)| (key.into_boxed_slice(), value.into_boxed_slice()))
+				.map_err(Into::into)
+				.boxed(),
+			Err(_) => stream::once(future::err(kvdb::Error::Backend("IndexedDB handle is poisoned".into()))).boxed(),
+		}
+	}
+
+	/// Wait until all the changes written so far are committed to the IndexedDB.
+	///
+	/// Fails if any commit has failed since the last flush, in which case the in-memory
+	/// database contains changes which are lost on reopening.
+	pub fn flush_async(&self) -> impl Future<Output = Result<(), Error>> {
+		let mut pending = lock(&self.pending);
+		if pending.count == 0 {
+			return future::Either::Left(future::ready(pending.failure.take().map_or(Ok(()), Err)));
+		}
+		let (tx, rx) = oneshot::channel();
+		pending.flushes.push(tx);
+		future::Either::Right(
+			rx.map(|result| result.unwrap_or_else(|_| Err(Error::CommitFailed("the flush was dropped".into()))))
+		)
 	}
 }
 
-impl Drop for Database {
+// Open the latest version of the IndexedDB, upgrading it if it lacks some of the columns.
+fn open_latest<B: Backend>(backend: B, name: String, columns: u32)
+	-> impl Future<Output = Result<Opened<B::Connection>, error::Error>>
+{
+	// let's try to open the latest version of the db first
+	backend.open(name.as_str(), None, columns).and_then(move |db| {
+
+		// If we need more column than the latest version has,
+		// then bump the version (+ 1 for the default column).
+		// In order to bump the version, we close the database
+		// and reopen it with a higher version than it was opened with previously.
+		// cf. https://github.com/paritytech/parity-common/pull/202#discussion_r321221751
+		if columns + 1 > db.columns {
+			let next_version = db.version + 1;
+			backend.close(&db.connection);
+			backend.open(name.as_str(), Some(next_version), columns)
+		} else {
+			future::ok(db).boxed()
+		}
+	})
+}
+
+impl<B: Backend> Drop for Database<B> {
 	fn drop(&mut self) {
 		if let Ok(db) = self.indexed_db.lock() {
-			db.close();
+			self.backend.close(&db);
 		}
 	}
 }
 
-impl KeyValueDB for Database {
-	fn get(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<DBValue>> {
-		self.in_memory.get(col, key)
+impl<B: Backend> KeyValueDB for Database<B> {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> kvdb::Result<Option<DBValue>> {
+		match self.storage {
+			Storage::Mirror(ref in_memory) => in_memory.get(col, key),
+			Storage::Lazy(ref cache) => {
+				self.check_column(col)?;
+				match lock(cache).values.get(&(col, key.to_vec())) {
+					Some(value) => Ok(value.clone()),
+					None => Err(kvdb::Error::Unsupported("The value is not cached, use `AsyncKeyValueDB::get`")),
+				}
+			},
+		}
+	}
+
+	fn get_many(&self, col: Option<u32>, keys: &[&[u8]]) -> kvdb::Result<Vec<Option<DBValue>>> {
+		match self.storage {
+			Storage::Mirror(ref in_memory) => in_memory.get_many(col, keys),
+			Storage::Lazy(_) => keys.iter().map(|key| KeyValueDB::get(self, col, key)).collect(),
+		}
 	}
 
-	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>> {
-		self.in_memory.get_by_prefix(col, prefix)
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> kvdb::Result<Option<Box<[u8]>>> {
+		self.in_memory()?.get_by_prefix(col, prefix)
 	}
 
 	fn write_buffered(&self, transaction: DBTransaction) {
-		if let Ok(guard) = self.indexed_db.lock() {
-			let _ = indexed_db::idb_commit_transaction(&*guard, &transaction, self.columns);
+		// failures are reported by the next flush.
+		match self.write_in_memory(&transaction) {
+			Ok(()) => self.commit(&transaction, |_| ()),
+			Err(err) => {
+				lock(&self.pending).failure.get_or_insert(err);
+			},
 		}
-		self.in_memory.write_buffered(transaction);
 	}
 
-	fn flush(&self) -> io::Result<()> {
-		Ok(())
+	// NOTE: can't wait for the pending commits without blocking the browser, so only
+	// the failures of the completed ones are reported. Use `flush_async` instead.
+	fn flush(&self) -> kvdb::Result<()> {
+		match lock(&self.pending).failure.take() {
+			Some(err) => Err(err.into()),
+			None => Ok(()),
+		}
 	}
 
 	// NOTE: clones the whole db
-	fn iter<'a>(&'a self, col: Option<u32>) -> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a> {
-		self.in_memory.iter(col)
+	fn iter<'a>(&'a self, col: Option<u32>) -> kvdb::Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>> {
+		self.in_memory()?.iter(col)
 	}
 
 	// NOTE: clones the whole db
 	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
-		-> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+		-> kvdb::Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>>
 	{
-		self.in_memory.iter_from_prefix(col, prefix)
+		self.in_memory()?.iter_from_prefix(col, prefix)
+	}
+
+	// NOTE: clones the whole range
+	fn iter_range<'a>(
+		&'a self,
+		col: Option<u32>,
+		start: Bound<&'a [u8]>,
+		end: Bound<&'a [u8]>,
+		direction: Direction,
+	) -> kvdb::Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>> {
+		self.in_memory()?.iter_range(col, start, end, direction)
+	}
+
+	fn column_stats(&self, col: Option<u32>) -> kvdb::Result<ColumnStats> {
+		self.in_memory()?.column_stats(col)
+	}
+
+	fn snapshot<'a>(&'a self) -> kvdb::Result<Box<dyn KeyValueDBSnapshot + 'a>> {
+		self.in_memory()?.snapshot()
 	}
 
 	// NOTE: not supported
-	fn restore(&self, _new_db: &str) -> std::io::Result<()> {
-		Err(io::Error::new(io::ErrorKind::Other, "Not supported yet"))
+	fn restore(&self, _new_db: &str) -> kvdb::Result<()> {
+		Err(kvdb::Error::Unsupported("Not supported yet"))
+	}
+}
+
+impl<B: Backend> AsyncKeyValueDB for Database<B> {
+	fn get<'a>(&'a self, col: Option<u32>, key: &'a [u8]) -> BoxFuture<'a, kvdb::Result<Option<DBValue>>> {
+		let cache = match self.storage {
+			Storage::Mirror(ref in_memory) => return future::ready(in_memory.get(col, key)).boxed(),
+			Storage::Lazy(ref cache) => cache,
+		};
+		if let Err(err) = self.check_column(col) {
+			return future::err(err).boxed();
+		}
+		let generation = {
+			let mut cache = lock(cache);
+			if let Some(value) = cache.values.get(&(col, key.to_vec())) {
+				return future::ok(value.clone()).boxed();
+			}
+			cache.fetching += 1;
+			cache.generation
+		};
+		let fetching = Fetching(cache);
+		let fetch = match self.indexed_db.lock() {
+			Ok(guard) => self.backend.get(&guard, indexed_db::column_to_number(col), key),
+			Err(_) => return future::err(kvdb::Error::Backend("IndexedDB handle is poisoned".into())).boxed(),
+		};
+		fetch.map(move |value| {
+			let _fetching = fetching;
+			let value = value?.map(DBValue::from_vec);
+			// the IndexedDB may have been read before a write of the key, which is newer.
+			let mut cache = lock(cache);
+			let cache_key = (col, key.to_vec());
+			if !cache.written_since(col, key, generation) && !cache.values.contains(&cache_key) {
+				cache.values.put(cache_key, value.clone());
+			}
+			Ok(value)
+		}).boxed()
+	}
+
+	fn write(&self, transaction: DBTransaction) -> BoxFuture<'_, kvdb::Result<()>> {
+		if let Err(err) = self.write_in_memory(&transaction) {
+			return future::err(err.into()).boxed();
+		}
+		let (tx, rx) = oneshot::channel();
+		self.commit(&transaction, move |result| {
+			let _ = tx.send(result);
+		});
+		rx.map(|result| match result {
+			Ok(result) => result.map_err(Into::into),
+			Err(_) => Err(kvdb::Error::Backend("The commit was dropped".into())),
+		}).boxed()
+	}
+
+	// NOTE: clones the whole column, unless opened lazily
+	fn iter(&self, col: Option<u32>) -> BoxStream<'_, kvdb::Result<(Box<[u8]>, Box<[u8]>)>> {
+		self.iter_range_async(col, Bound::Unbounded, Bound::Unbounded, Direction::Forward)
 	}
 }
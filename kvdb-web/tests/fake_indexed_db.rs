@@ -0,0 +1,234 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests of `Database` with the in-process fake of the IndexedDB.
+
+use std::ops::Bound;
+
+use futures::executor::block_on;
+use futures::stream::StreamExt as _;
+use kvdb::Direction;
+
+use kvdb_web::{Database, FakeIndexedDB, KeyValueDB};
+
+fn open(backend: &FakeIndexedDB, name: &str, columns: u32) -> Database<FakeIndexedDB> {
+	block_on(Database::open_with(backend.clone(), name.into(), columns)).unwrap()
+}
+
+#[test]
+fn reopen_the_database_with_more_columns() {
+	let backend = FakeIndexedDB::new();
+	let db = open(&backend, "test", 1);
+	assert_eq!(db.version(), 1);
+
+	let mut batch = db.transaction();
+	batch.put(None, b"hello", b"world");
+	batch.put(Some(0), b"hi", b"there");
+	db.write_buffered(batch);
+	block_on(db.flush_async()).unwrap();
+	drop(db);
+
+	let db = open(&backend, "test", 3);
+	assert_eq!(db.version(), 2);
+	assert_eq!(db.num_columns(), 3);
+	assert_eq!(db.get(None, b"hello").unwrap().unwrap().as_ref(), b"world");
+	assert_eq!(db.get(Some(0), b"hi").unwrap().unwrap().as_ref(), b"there");
+	assert!(db.get(Some(2), b"hi").unwrap().is_none());
+	drop(db);
+
+	// fewer columns don't need an upgrade.
+	let db = open(&backend, "test", 1);
+	assert_eq!(db.version(), 2);
+	assert!(db.get(Some(2), b"hi").is_err());
+}
+
+#[test]
+fn writes_are_persisted() {
+	use kvdb_web::AsyncKeyValueDB;
+
+	let backend = FakeIndexedDB::new();
+	let db = open(&backend, "test", 1);
+	let mut batch = db.transaction();
+	batch.put(Some(0), b"a", b"1");
+	batch.put(Some(0), b"ab", b"2");
+	batch.put(Some(0), b"b", b"3");
+	db.write_buffered(batch);
+	let mut batch = db.transaction();
+	batch.delete_prefix(Some(0), b"a");
+	batch.put(None, b"c", b"4");
+	block_on(AsyncKeyValueDB::write(&db, batch)).unwrap();
+	drop(db);
+
+	let db = open(&backend, "test", 1);
+	assert_eq!(KeyValueDB::get(&db, None, b"c").unwrap().unwrap().as_ref(), b"4");
+	let items: Vec<_> = KeyValueDB::iter(&db, Some(0)).unwrap().collect();
+	assert_eq!(items, vec![(b"b".to_vec().into_boxed_slice(), b"3".to_vec().into_boxed_slice())]);
+}
+
+#[test]
+fn failed_commits_are_reported_on_flush() {
+	let backend = FakeIndexedDB::new();
+	let db = open(&backend, "test", 1);
+	backend.set_fail_commits(true);
+	let mut batch = db.transaction();
+	batch.put(Some(0), b"hello", b"world");
+	db.write_buffered(batch);
+	assert!(block_on(db.flush_async()).is_err());
+	// the failure is only reported once.
+	assert!(block_on(db.flush_async()).is_ok());
+	drop(db);
+
+	backend.set_fail_commits(false);
+	let db = open(&backend, "test", 1);
+	assert!(db.get(Some(0), b"hello").unwrap().is_none());
+}
+
+#[test]
+fn failed_async_writes_are_reported() {
+	use kvdb_web::AsyncKeyValueDB;
+
+	let backend = FakeIndexedDB::new();
+	let db = open(&backend, "test", 1);
+	backend.set_fail_commits(true);
+	let mut batch = db.transaction();
+	batch.put(Some(0), b"hello", b"world");
+	assert!(block_on(AsyncKeyValueDB::write(&db, batch)).is_err());
+
+	backend.set_fail_commits(false);
+	let mut batch = db.transaction();
+	batch.put(Some(0), b"hi", b"there");
+	block_on(AsyncKeyValueDB::write(&db, batch)).unwrap();
+}
+
+#[test]
+fn lazy_database_reads_from_the_backend() {
+	use kvdb_web::AsyncKeyValueDB;
+
+	let backend = FakeIndexedDB::new();
+	let db = open(&backend, "test", 1);
+	let mut batch = db.transaction();
+	batch.put(Some(0), b"hello", b"world");
+	batch.put(Some(0), b"hi", b"there");
+	db.write_buffered(batch);
+	drop(db);
+
+	let db = block_on(Database::open_lazy_with(backend.clone(), "test".into(), 1, 16)).unwrap();
+	// nothing is cached yet.
+	assert!(KeyValueDB::get(&db, Some(0), b"hello").is_err());
+	let value = block_on(AsyncKeyValueDB::get(&db, Some(0), b"hello")).unwrap();
+	assert_eq!(value.unwrap().as_ref(), b"world");
+	assert_eq!(KeyValueDB::get(&db, Some(0), b"hello").unwrap().unwrap().as_ref(), b"world");
+
+	let mut batch = db.transaction();
+	batch.delete(Some(0), b"hi");
+	db.write_buffered(batch);
+	let keys: Vec<_> = block_on(
+		db.iter_range_async(Some(0), Bound::Unbounded, Bound::Unbounded, Direction::Reverse)
+			.map(|item| item.unwrap().0)
+			.collect::<Vec<_>>()
+	);
+	assert_eq!(keys, vec![b"hello".to_vec().into_boxed_slice()]);
+}
+
+#[test]
+fn lazy_database_does_not_cache_values_overwritten_while_fetching() {
+	use kvdb_web::AsyncKeyValueDB;
+
+	let backend = FakeIndexedDB::new();
+	let db = open(&backend, "test", 1);
+	let mut batch = db.transaction();
+	batch.put(Some(0), b"hello", b"world");
+	db.write_buffered(batch);
+	drop(db);
+
+	let db = block_on(Database::open_lazy_with(backend.clone(), "test".into(), 1, 1)).unwrap();
+	// the fake reads the value when the fetch starts.
+	let fetch = AsyncKeyValueDB::get(&db, Some(0), b"hello");
+	let mut batch = db.transaction();
+	batch.put(Some(0), b"hello", b"there");
+	// evicts the new value from the cache.
+	batch.put(Some(0), b"hi", b"there");
+	db.write_buffered(batch);
+	assert_eq!(block_on(fetch).unwrap().unwrap().as_ref(), b"world");
+
+	assert!(KeyValueDB::get(&db, Some(0), b"hello").is_err());
+	let value = block_on(AsyncKeyValueDB::get(&db, Some(0), b"hello")).unwrap();
+	assert_eq!(value.unwrap().as_ref(), b"there");
+}
+
+#[test]
+fn add_and_drop_columns() {
+	let backend = FakeIndexedDB::new();
+	let db = open(&backend, "test", 1);
+
+	block_on(db.add_column()).unwrap();
+	assert_eq!(db.num_columns(), 2);
+	assert_eq!(db.version(), 2);
+	let mut batch = db.transaction();
+	batch.put(Some(1), b"hello", b"world");
+	db.write_buffered(batch);
+	block_on(db.flush_async()).unwrap();
+
+	block_on(db.drop_column()).unwrap();
+	assert_eq!(db.num_columns(), 1);
+	assert_eq!(db.version(), 3);
+	assert!(db.get(Some(1), b"hello").is_err());
+
+	// the dropped column is empty when it is added again.
+	block_on(db.add_column()).unwrap();
+	drop(db);
+	let db = open(&backend, "test", 2);
+	assert_eq!(db.version(), 4);
+	assert!(db.get(Some(1), b"hello").unwrap().is_none());
+}
+
+#[test]
+fn upgrades_close_the_other_connections() {
+	let backend = FakeIndexedDB::new();
+	let db = open(&backend, "test", 1);
+	let other = open(&backend, "test", 1);
+
+	block_on(db.add_column()).unwrap();
+	let mut batch = other.transaction();
+	batch.put(Some(0), b"hello", b"world");
+	other.write_buffered(batch);
+	assert!(block_on(other.flush_async()).is_err());
+
+	// the connection replacing the closed one is used.
+	let mut batch = db.transaction();
+	batch.put(Some(1), b"hello", b"world");
+	db.write_buffered(batch);
+	block_on(db.flush_async()).unwrap();
+}
+
+#[test]
+fn merges_are_rejected() {
+	use kvdb_web::AsyncKeyValueDB;
+
+	let backend = FakeIndexedDB::new();
+	let db = open(&backend, "test", 1);
+	let lazy = block_on(Database::open_lazy_with(backend.clone(), "lazy".into(), 1, 16)).unwrap();
+	for db in &[db, lazy] {
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"hello", b"world");
+		batch.merge(Some(0), b"hello", b"!");
+		assert!(block_on(AsyncKeyValueDB::write(db, batch.clone())).is_err());
+		db.write_buffered(batch);
+		assert!(block_on(db.flush_async()).is_err());
+		// nothing of the transactions is written.
+		assert!(block_on(AsyncKeyValueDB::get(db, Some(0), b"hello")).unwrap().is_none());
+	}
+}
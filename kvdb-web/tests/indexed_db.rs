@@ -16,10 +16,14 @@
 
 //! IndexedDB tests.
 
+use std::ops::Bound;
+
 use futures::compat;
 use futures::future::{self, FutureExt as _, TryFutureExt as _};
+use futures::stream::StreamExt as _;
+use kvdb::Direction;
 
-use kvdb_web::{Database, KeyValueDB as _};
+use kvdb_web::{Database, KeyValueDB};
 
 use wasm_bindgen_test::*;
 use wasm_bindgen::JsValue;
@@ -64,3 +68,92 @@ fn reopen_the_database_with_more_columns() -> impl futures01::Future<Item = (),
 
 	compat::Compat::new(fut)
 }
+
+#[wasm_bindgen_test(async)]
+fn flush_waits_for_the_commits() -> impl futures01::Future<Item = (), Error = JsValue> {
+	let fut = Database::open("MyFlushTest".into(), 1)
+		.unwrap_or_else(|err| panic!("{}", err))
+		.then(|db| {
+			let mut batch = db.transaction();
+			batch.put(Some(0), b"hello", b"world");
+			db.write_buffered(batch);
+			db.flush_async().map(move |res| (db, res))
+		})
+		.then(|(db, res)| {
+			res.unwrap_or_else(|err| panic!("{}", err));
+			drop(db);
+			Database::open("MyFlushTest".into(), 1).unwrap_or_else(|err| panic!("{}", err))
+		})
+		.map(|db| {
+			assert_eq!(db.get(Some(0), b"hello").unwrap().unwrap().as_ref(), b"world");
+			Ok(())
+		});
+
+	compat::Compat::new(fut)
+}
+
+#[wasm_bindgen_test(async)]
+fn lazy_database_reads_from_indexed_db() -> impl futures01::Future<Item = (), Error = JsValue> {
+	use kvdb_web::AsyncKeyValueDB;
+
+	let fut = async {
+		let db = Database::open("MyLazyTest".into(), 1).await.unwrap_or_else(|err| panic!("{}", err));
+		let mut batch = db.transaction();
+		batch.put(Some(0), b"hello", b"world");
+		batch.put(Some(0), b"hi", b"there");
+		db.write_buffered(batch);
+		db.flush_async().await.unwrap_or_else(|err| panic!("{}", err));
+		drop(db);
+
+		let db = Database::open_lazy("MyLazyTest".into(), 1, 16).await.unwrap_or_else(|err| panic!("{}", err));
+		// nothing is cached yet.
+		assert!(KeyValueDB::get(&db, Some(0), b"hello").is_err());
+		let value = AsyncKeyValueDB::get(&db, Some(0), b"hello").await.unwrap();
+		assert_eq!(value.unwrap().as_ref(), b"world");
+		assert_eq!(KeyValueDB::get(&db, Some(0), b"hello").unwrap().unwrap().as_ref(), b"world");
+
+		let mut batch = db.transaction();
+		batch.delete(Some(0), b"hi");
+		db.write_buffered(batch);
+		assert!(KeyValueDB::get(&db, Some(0), b"hi").unwrap().is_none());
+
+		let keys: Vec<_> = db.iter_range_async(Some(0), Bound::Unbounded, Bound::Unbounded, Direction::Forward)
+			.map(|item| item.unwrap().0)
+			.collect()
+			.await;
+		assert_eq!(keys, vec![b"hello".to_vec().into_boxed_slice()]);
+		Ok::<_, JsValue>(())
+	};
+
+	compat::Compat::new(fut.boxed_local())
+}
+
+#[wasm_bindgen_test(async)]
+fn add_and_drop_columns() -> impl futures01::Future<Item = (), Error = JsValue> {
+	let fut = async {
+		let db = Database::open("MyColumnsTest".into(), 1).await.unwrap_or_else(|err| panic!("{}", err));
+		let version = db.version();
+
+		db.add_column().await.unwrap_or_else(|err| panic!("{}", err));
+		assert_eq!(db.num_columns(), 2);
+		assert_eq!(db.version(), version + 1);
+		let mut batch = db.transaction();
+		batch.put(Some(1), b"hello", b"world");
+		db.write_buffered(batch);
+		db.flush_async().await.unwrap_or_else(|err| panic!("{}", err));
+
+		db.drop_column().await.unwrap_or_else(|err| panic!("{}", err));
+		assert_eq!(db.num_columns(), 1);
+		assert_eq!(db.version(), version + 2);
+		assert!(db.get(Some(1), b"hello").is_err());
+
+		// the dropped column is empty when it is added again.
+		db.add_column().await.unwrap_or_else(|err| panic!("{}", err));
+		drop(db);
+		let db = Database::open("MyColumnsTest".into(), 2).await.unwrap_or_else(|err| panic!("{}", err));
+		assert!(db.get(Some(1), b"hello").unwrap().is_none());
+		Ok::<_, JsValue>(())
+	};
+
+	compat::Compat::new(fut.boxed_local())
+}
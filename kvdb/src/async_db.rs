@@ -0,0 +1,87 @@
+// Copyright 2015-2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Asynchronous key-value database interface.
+
+use futures::prelude::*;
+use futures::future::{self, BoxFuture};
+use futures::stream::{self, BoxStream};
+
+use crate::{DBTransaction, DBValue, KeyValueDB, Result};
+
+/// Asynchronous counterpart of `KeyValueDB`.
+///
+/// Intended for backends which perform I/O asynchronously, and for applications which
+/// can't afford to block their executor on database access.
+pub trait AsyncKeyValueDB: Sync + Send {
+	/// Get a value by key.
+	fn get<'a>(&'a self, col: Option<u32>, key: &'a [u8]) -> BoxFuture<'a, Result<Option<DBValue>>>;
+
+	/// Write a transaction of changes to the backing store.
+	/// Resolves once the changes have been written.
+	fn write(&self, transaction: DBTransaction) -> BoxFuture<'_, Result<()>>;
+
+	/// Stream over flushed data for a given column.
+	fn iter(&self, col: Option<u32>) -> BoxStream<'_, Result<(Box<[u8]>, Box<[u8]>)>>;
+}
+
+/// Exposes a synchronous `KeyValueDB` through the `AsyncKeyValueDB` interface.
+///
+/// The wrapped database is only accessed once the returned futures are polled, but the calls
+/// still block the polling thread. This is fine for in-memory databases; anything doing disk
+/// I/O should be polled on an executor dedicated to blocking work.
+pub struct AsyncAdapter<T> {
+	inner: T,
+}
+
+impl<T: KeyValueDB> AsyncAdapter<T> {
+	/// Wrap a synchronous database.
+	pub fn new(inner: T) -> Self {
+		AsyncAdapter { inner }
+	}
+
+	/// Get a reference to the wrapped database.
+	pub fn inner(&self) -> &T {
+		&self.inner
+	}
+
+	/// Unwrap the synchronous database.
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+}
+
+impl<T: KeyValueDB> AsyncKeyValueDB for AsyncAdapter<T> {
+	fn get<'a>(&'a self, col: Option<u32>, key: &'a [u8]) -> BoxFuture<'a, Result<Option<DBValue>>> {
+		future::lazy(move |_| self.inner.get(col, key)).boxed()
+	}
+
+	fn write(&self, transaction: DBTransaction) -> BoxFuture<'_, Result<()>> {
+		future::lazy(move |_| self.inner.write(transaction)).boxed()
+	}
+
+	fn iter(&self, col: Option<u32>) -> BoxStream<'_, Result<(Box<[u8]>, Box<[u8]>)>> {
+		future::lazy(move |_| {
+			let items = match self.inner.iter(col) {
+				Ok(iter) => iter.map(Ok).collect(),
+				Err(err) => vec![Err(err)],
+			};
+			stream::iter(items)
+		})
+			.flatten_stream()
+			.boxed()
+	}
+}
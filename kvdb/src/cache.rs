@@ -0,0 +1,335 @@
+// Copyright 2015-2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Caching of the values read from a `KeyValueDB`.
+
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Bound;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use lru::LruCache;
+
+use crate::migration::ColumnManager;
+use crate::{ColumnStats, DBOp, DBTransaction, DBValue, Direction, KeyValueDB, KeyValueDBSnapshot, Result};
+
+/// Statistics of a `CachedDB`, accumulated since it was created.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CacheStats {
+	/// Number of keys read from the cache.
+	pub hits: u64,
+	/// Number of keys read from the wrapped database.
+	pub misses: u64,
+	/// Size of the cached entries of all the columns, in bytes, as counted against the capacities.
+	pub size: u64,
+}
+
+/// Wraps a `KeyValueDB`, keeping recently read values in memory.
+///
+/// Each column has a least recently used cache bounded by the size of its keys and values,
+/// plus a fixed overhead per entry. Missing keys are cached too.
+/// Cached values are invalidated by the writes through the `CachedDB`, so changes must not
+/// be written to the wrapped database directly. Iteration and snapshots bypass the cache.
+pub struct CachedDB<D> {
+	db: D,
+	capacity: usize,
+	column_capacity: HashMap<Option<u32>, usize>,
+	caches: Mutex<Caches>,
+	hits: AtomicU64,
+	misses: AtomicU64,
+}
+
+#[derive(Default)]
+struct Caches {
+	columns: HashMap<Option<u32>, ColumnCache>,
+	// Bumped by writes, so values read before a write are not cached after it.
+	generation: u64,
+}
+
+struct ColumnCache {
+	// Values by key, with the memory used by the entries.
+	entries: LruCache<Vec<u8>, (Option<Vec<u8>>, usize)>,
+	size: usize,
+	capacity: usize,
+}
+
+// Memory used by a cache entry besides its key and value, roughly.
+const ENTRY_OVERHEAD: usize = mem::size_of::<(Vec<u8>, (Option<Vec<u8>>, usize))>();
+
+// The caches are only changed by code which doesn't panic, so poisoning is ignored.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+	mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl ColumnCache {
+	fn new(capacity: usize) -> Self {
+		ColumnCache {
+			entries: LruCache::unbounded(),
+			size: 0,
+			capacity,
+		}
+	}
+
+	fn insert(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+		self.remove(&key);
+		let size = key.len() + value.as_ref().map_or(0, Vec::len) + ENTRY_OVERHEAD;
+		if size > self.capacity {
+			return;
+		}
+		while self.size + size > self.capacity {
+			match self.entries.pop_lru() {
+				Some((_, (_, evicted))) => self.size -= evicted,
+				None => break,
+			}
+		}
+		self.entries.put(key, (value, size));
+		self.size += size;
+	}
+
+	fn remove(&mut self, key: &[u8]) {
+		if let Some((_, size)) = self.entries.pop(key) {
+			self.size -= size;
+		}
+	}
+
+	fn remove_prefix(&mut self, prefix: &[u8]) {
+		let keys: Vec<_> = self.entries.iter()
+			.filter(|&(key, _)| key.starts_with(prefix))
+			.map(|(key, _)| key.clone())
+			.collect();
+		for key in keys {
+			self.remove(&key);
+		}
+	}
+}
+
+impl<D: KeyValueDB> CachedDB<D> {
+	/// Wrap a database, caching up to `capacity` bytes of every column.
+	pub fn new(db: D, capacity: usize) -> Self {
+		CachedDB {
+			db,
+			capacity,
+			column_capacity: HashMap::new(),
+			caches: Mutex::new(Caches::default()),
+			hits: AtomicU64::new(0),
+			misses: AtomicU64::new(0),
+		}
+	}
+
+	/// Cache up to `capacity` bytes of the given column instead.
+	pub fn with_column_capacity(mut self, col: Option<u32>, capacity: usize) -> Self {
+		self.column_capacity.insert(col, capacity);
+		lock(&self.caches).columns.remove(&col);
+		self
+	}
+
+	/// Get a reference to the wrapped database.
+	pub fn inner(&self) -> &D {
+		&self.db
+	}
+
+	/// Unwrap the database.
+	pub fn into_inner(self) -> D {
+		self.db
+	}
+
+	/// Get the statistics of the cache.
+	pub fn cache_stats(&self) -> CacheStats {
+		let size = lock(&self.caches).columns.values().map(|cache| cache.size as u64).sum();
+		CacheStats {
+			hits: self.hits.load(Ordering::Relaxed),
+			misses: self.misses.load(Ordering::Relaxed),
+			size,
+		}
+	}
+
+	fn column_cache<'a>(&self, caches: &'a mut Caches, col: Option<u32>) -> &'a mut ColumnCache {
+		let capacity = self.column_capacity.get(&col).cloned().unwrap_or(self.capacity);
+		caches.columns.entry(col).or_insert_with(|| ColumnCache::new(capacity))
+	}
+
+	// Cache the values read from the database, unless they were changed in the meantime.
+	fn insert_read(&self, col: Option<u32>, generation: u64, values: Vec<(&[u8], Option<&DBValue>)>) {
+		let mut caches = lock(&self.caches);
+		if caches.generation != generation {
+			return;
+		}
+		let cache = self.column_cache(&mut caches, col);
+		for (key, value) in values {
+			cache.insert(key.to_vec(), value.map(|v| v.to_vec()));
+		}
+	}
+
+	// Drop the cached values of the keys changed by a transaction.
+	fn invalidate(&self, changed: Vec<(Option<u32>, Vec<u8>, bool)>) {
+		let mut caches = lock(&self.caches);
+		caches.generation += 1;
+		for (col, key, is_prefix) in changed {
+			if let Some(cache) = caches.columns.get_mut(&col) {
+				if is_prefix {
+					cache.remove_prefix(&key);
+				} else {
+					cache.remove(&key);
+				}
+			}
+		}
+	}
+
+	// Drop all the cached values.
+	fn clear(&self) {
+		let mut caches = lock(&self.caches);
+		caches.generation += 1;
+		caches.columns.clear();
+	}
+}
+
+// The keys changed by a transaction, with whether they are prefixes of the changed keys.
+fn changed_keys(transaction: &DBTransaction) -> Vec<(Option<u32>, Vec<u8>, bool)> {
+	transaction.ops.iter().map(|op| match *op {
+		DBOp::DeletePrefix { col, ref prefix } => (col, prefix.to_vec(), true),
+		_ => (op.col(), op.key().to_vec(), false),
+	}).collect()
+}
+
+impl<D: KeyValueDB> KeyValueDB for CachedDB<D> {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>> {
+		let generation = {
+			let mut caches = lock(&self.caches);
+			if let Some((value, _)) = caches.columns.get_mut(&col).and_then(|cache| cache.entries.get(key)) {
+				self.hits.fetch_add(1, Ordering::Relaxed);
+				return Ok(value.as_ref().map(|v| DBValue::from_slice(v)));
+			}
+			caches.generation
+		};
+		self.misses.fetch_add(1, Ordering::Relaxed);
+		let value = self.db.get(col, key)?;
+		self.insert_read(col, generation, vec![(key, value.as_ref())]);
+		Ok(value)
+	}
+
+	fn get_many(&self, col: Option<u32>, keys: &[&[u8]]) -> Result<Vec<Option<DBValue>>> {
+		let mut values = Vec::with_capacity(keys.len());
+		let mut missing = Vec::new();
+		let generation = {
+			let mut caches = lock(&self.caches);
+			let mut cache = caches.columns.get_mut(&col);
+			for (i, key) in keys.iter().enumerate() {
+				let cached = cache.as_mut().and_then(|cache| cache.entries.get(*key));
+				match cached {
+					Some((value, _)) => values.push(value.as_ref().map(|v| DBValue::from_slice(v))),
+					None => {
+						values.push(None);
+						missing.push(i);
+					},
+				}
+			}
+			caches.generation
+		};
+		self.hits.fetch_add((keys.len() - missing.len()) as u64, Ordering::Relaxed);
+		if missing.is_empty() {
+			return Ok(values);
+		}
+		self.misses.fetch_add(missing.len() as u64, Ordering::Relaxed);
+
+		let missing_keys: Vec<_> = missing.iter().map(|&i| keys[i]).collect();
+		let read = self.db.get_many(col, &missing_keys)?;
+		self.insert_read(col, generation, missing_keys.iter().cloned().zip(read.iter().map(Option::as_ref)).collect());
+		for (i, value) in missing.into_iter().zip(read) {
+			values[i] = value;
+		}
+		Ok(values)
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Result<Option<Box<[u8]>>> {
+		self.db.get_by_prefix(col, prefix)
+	}
+
+	fn write_buffered(&self, transaction: DBTransaction) {
+		let changed = changed_keys(&transaction);
+		self.db.write_buffered(transaction);
+		self.invalidate(changed);
+	}
+
+	fn write(&self, transaction: DBTransaction) -> Result<()> {
+		let changed = changed_keys(&transaction);
+		let result = self.db.write(transaction);
+		// the transaction may have been written partially.
+		self.invalidate(changed);
+		result
+	}
+
+	fn flush(&self) -> Result<()> {
+		self.db.flush()
+	}
+
+	fn iter<'a>(&'a self, col: Option<u32>)
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>>
+	{
+		self.db.iter(col)
+	}
+
+	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>>
+	{
+		self.db.iter_from_prefix(col, prefix)
+	}
+
+	fn iter_range<'a>(
+		&'a self,
+		col: Option<u32>,
+		start: Bound<&'a [u8]>,
+		end: Bound<&'a [u8]>,
+		direction: Direction,
+	) -> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>> {
+		self.db.iter_range(col, start, end, direction)
+	}
+
+	fn column_stats(&self, col: Option<u32>) -> Result<ColumnStats> {
+		self.db.column_stats(col)
+	}
+
+	fn snapshot<'a>(&'a self) -> Result<Box<dyn KeyValueDBSnapshot + 'a>> {
+		self.db.snapshot()
+	}
+
+	fn restore(&self, new_db: &str) -> Result<()> {
+		let result = self.db.restore(new_db);
+		self.clear();
+		result
+	}
+}
+
+impl<D: KeyValueDB + ColumnManager> ColumnManager for CachedDB<D> {
+	fn num_columns(&self) -> u32 {
+		self.db.num_columns()
+	}
+
+	fn add_column(&self) -> Result<()> {
+		self.db.add_column()
+	}
+
+	fn drop_column(&self) -> Result<()> {
+		let dropped = self.db.num_columns().checked_sub(1);
+		let result = self.db.drop_column();
+		if dropped.is_some() {
+			let mut caches = lock(&self.caches);
+			caches.generation += 1;
+			caches.columns.remove(&dropped);
+		}
+		result
+	}
+}
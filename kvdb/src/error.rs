@@ -0,0 +1,88 @@
+// Copyright 2015-2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Errors that can occur when working with a key-value database.
+
+use std::{error, fmt, io, result};
+
+/// Result type returned by `KeyValueDB` methods.
+pub type Result<T> = result::Result<T, Error>;
+
+/// An error that occurred when working with a key-value database.
+#[derive(Debug)]
+pub enum Error {
+	/// The column does not exist in the database.
+	UnknownColumn(Option<u32>),
+	/// The database has been closed.
+	Closed,
+	/// The database is corrupted.
+	Corruption(String),
+	/// An I/O error.
+	Io(io::Error),
+	/// Any other error reported by the database backend.
+	Backend(String),
+	/// The operation is not supported by the database.
+	Unsupported(&'static str),
+	/// The database was opened read-only and cannot be written to.
+	ReadOnly,
+	/// A migration of the database failed.
+	Migration(String),
+	/// This enum may grow additional variants,
+	/// so this makes sure clients don't count on exhaustive matching.
+	/// (Otherwise, adding a new variant could break existing code.)
+	#[doc(hidden)]
+	__Nonexhaustive,
+}
+
+impl error::Error for Error {
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		match *self {
+			Error::Io(ref err) => Some(err),
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::UnknownColumn(col) => write!(f, "No such column family: {:?}", col),
+			Error::Closed => write!(f, "Database is closed"),
+			Error::Corruption(ref err) => write!(f, "Database is corrupted: {}", err),
+			Error::Io(ref err) => write!(f, "I/O error: {}", err),
+			Error::Backend(ref err) => write!(f, "Database error: {}", err),
+			Error::Unsupported(op) => write!(f, "Operation not supported: {}", op),
+			Error::ReadOnly => write!(f, "Database is opened read-only"),
+			Error::Migration(ref err) => write!(f, "Migration failed: {}", err),
+			Error::__Nonexhaustive => unreachable!(),
+		}
+	}
+}
+
+impl From<io::Error> for Error {
+	fn from(err: io::Error) -> Self {
+		Error::Io(err)
+	}
+}
+
+impl From<Error> for io::Error {
+	fn from(err: Error) -> Self {
+		match err {
+			Error::Io(err) => err,
+			err => io::Error::new(io::ErrorKind::Other, err),
+		}
+	}
+}
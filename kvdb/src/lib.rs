@@ -16,23 +16,111 @@
 
 //! Key-Value store abstraction with `RocksDB` backend.
 
-use std::io;
+#[cfg(feature = "async")]
+mod async_db;
+#[cfg(feature = "cache")]
+mod cache;
+mod error;
+pub mod migration;
+
+use std::ops::Bound;
 use std::path::Path;
 use std::sync::Arc;
 use elastic_array::{ElasticArray128, ElasticArray32};
 use bytes::Bytes;
 
+#[cfg(feature = "async")]
+pub use async_db::{AsyncKeyValueDB, AsyncAdapter};
+#[cfg(feature = "cache")]
+pub use cache::{CachedDB, CacheStats};
+pub use error::{Error, Result};
+
 /// Required length of prefixes.
 pub const PREFIX_LEN: usize = 12;
 
 /// Database value.
 pub type DBValue = ElasticArray128<u8>;
 
+/// Direction of iteration over a range of keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+	/// Visit keys in ascending order.
+	Forward,
+	/// Visit keys in descending order.
+	Reverse,
+}
+
+/// Returns true if `key` is not below the `start` bound.
+pub fn is_after_start(key: &[u8], start: Bound<&[u8]>) -> bool {
+	match start {
+		Bound::Included(start) => key >= start,
+		Bound::Excluded(start) => key > start,
+		Bound::Unbounded => true,
+	}
+}
+
+/// Returns true if `key` is not above the `end` bound.
+pub fn is_before_end(key: &[u8], end: Bound<&[u8]>) -> bool {
+	match end {
+		Bound::Included(end) => key <= end,
+		Bound::Excluded(end) => key < end,
+		Bound::Unbounded => true,
+	}
+}
+
+/// Handle of a database column, usable in place of the `Option<u32>` column index.
+///
+/// The default column is `ColumnId::DEFAULT`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ColumnId(Option<u32>);
+
+impl ColumnId {
+	/// The default column.
+	pub const DEFAULT: ColumnId = ColumnId(None);
+
+	/// The column with the given index.
+	pub fn new(index: u32) -> ColumnId {
+		ColumnId(Some(index))
+	}
+
+	/// The column index, `None` for the default column.
+	pub fn index(&self) -> Option<u32> {
+		self.0
+	}
+}
+
+impl From<Option<u32>> for ColumnId {
+	fn from(col: Option<u32>) -> ColumnId {
+		ColumnId(col)
+	}
+}
+
+impl From<ColumnId> for Option<u32> {
+	fn from(col: ColumnId) -> Option<u32> {
+		col.0
+	}
+}
+
+/// How durably changes are written, for backends which support it.
+///
+/// Ordered from the weakest to the strongest guarantee.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Durability {
+	/// Skip the write-ahead log. Changes not yet persisted by the backend are lost on a crash.
+	NoWal,
+	/// Write to the write-ahead log. Changes survive a crash of the process, but not of the system.
+	Wal,
+	/// Write to the write-ahead log and sync it to disk.
+	WalSynced,
+}
+
 /// Write transaction. Batches a sequence of put/delete operations for efficiency.
 #[derive(Default, Clone, PartialEq)]
 pub struct DBTransaction {
 	/// Database operations.
 	pub ops: Vec<DBOp>,
+	/// Durability of the transaction, overriding the database default.
+	pub durability: Option<Durability>,
 }
 
 /// Database operation.
@@ -46,15 +134,34 @@ pub enum DBOp {
 	Delete {
 		col: Option<u32>,
 		key: ElasticArray32<u8>,
-	}
+	},
+	DeletePrefix {
+		col: Option<u32>,
+		prefix: ElasticArray32<u8>,
+	},
+	Merge {
+		col: Option<u32>,
+		key: ElasticArray32<u8>,
+		operand: DBValue,
+	},
 }
 
+/// Associative merge function of a column, applying `DBOp::Merge` operands to a value.
+///
+/// Called with the key, the existing value if any, and the operands in the order they were
+/// written. Returns the new value. Since the function is associative, the result of merging
+/// some operands without an existing value may be passed as a single operand later on.
+pub type MergeFn = Arc<dyn Fn(&[u8], Option<&[u8]>, &[&[u8]]) -> Vec<u8> + Send + Sync>;
+
 impl DBOp {
 	/// Returns the key associated with this operation.
+	/// For `DeletePrefix` this is the deleted prefix.
 	pub fn key(&self) -> &[u8] {
 		match *self {
 			DBOp::Insert { ref key, .. } => key,
 			DBOp::Delete { ref key, .. } => key,
+			DBOp::DeletePrefix { ref prefix, .. } => prefix,
+			DBOp::Merge { ref key, .. } => key,
 		}
 	}
 
@@ -63,6 +170,8 @@ impl DBOp {
 		match *self {
 			DBOp::Insert { col, .. } => col,
 			DBOp::Delete { col, .. } => col,
+			DBOp::DeletePrefix { col, .. } => col,
+			DBOp::Merge { col, .. } => col,
 		}
 	}
 }
@@ -76,43 +185,118 @@ impl DBTransaction {
 	/// Create new transaction with capacity.
 	pub fn with_capacity(cap: usize) -> DBTransaction {
 		DBTransaction {
-			ops: Vec::with_capacity(cap)
+			ops: Vec::with_capacity(cap),
+			durability: None,
 		}
 	}
 
+	/// Set the durability of the transaction, overriding the database default.
+	pub fn set_durability(&mut self, durability: Durability) {
+		self.durability = Some(durability);
+	}
+
 	/// Insert a key-value pair in the transaction. Any existing value will be overwritten upon write.
-	pub fn put(&mut self, col: Option<u32>, key: &[u8], value: &[u8]) {
+	pub fn put(&mut self, col: impl Into<Option<u32>>, key: &[u8], value: &[u8]) {
 		let mut ekey = ElasticArray32::new();
 		ekey.append_slice(key);
 		self.ops.push(DBOp::Insert {
-			col: col,
+			col: col.into(),
 			key: ekey,
 			value: DBValue::from_slice(value),
 		});
 	}
 
 	/// Insert a key-value pair in the transaction. Any existing value will be overwritten upon write.
-	pub fn put_vec(&mut self, col: Option<u32>, key: &[u8], value: Bytes) {
+	pub fn put_vec(&mut self, col: impl Into<Option<u32>>, key: &[u8], value: Bytes) {
 		let mut ekey = ElasticArray32::new();
 		ekey.append_slice(key);
 		self.ops.push(DBOp::Insert {
-			col: col,
+			col: col.into(),
 			key: ekey,
 			value: DBValue::from_vec(value),
 		});
 	}
 
 	/// Delete value by key.
-	pub fn delete(&mut self, col: Option<u32>, key: &[u8]) {
+	pub fn delete(&mut self, col: impl Into<Option<u32>>, key: &[u8]) {
 		let mut ekey = ElasticArray32::new();
 		ekey.append_slice(key);
 		self.ops.push(DBOp::Delete {
-			col: col,
+			col: col.into(),
+			key: ekey,
+		});
+	}
+
+	/// Merge an operand into the value of a key, using the merge function registered
+	/// for the column with the database.
+	pub fn merge(&mut self, col: impl Into<Option<u32>>, key: &[u8], operand: &[u8]) {
+		let mut ekey = ElasticArray32::new();
+		ekey.append_slice(key);
+		self.ops.push(DBOp::Merge {
+			col: col.into(),
 			key: ekey,
+			operand: DBValue::from_slice(operand),
+		});
+	}
+
+	/// Delete all values with the given key prefix.
+	/// Using an empty prefix will delete all values in the column.
+	pub fn delete_prefix(&mut self, col: impl Into<Option<u32>>, prefix: &[u8]) {
+		let mut eprefix = ElasticArray32::new();
+		eprefix.append_slice(prefix);
+		self.ops.push(DBOp::DeletePrefix {
+			col: col.into(),
+			prefix: eprefix,
 		});
 	}
 }
 
+/// Returns the smallest key which is greater than every key starting with `prefix`,
+/// or `None` if there is no such key, i.e. the prefix is empty or consists of `0xff` bytes only.
+pub fn end_prefix(prefix: &[u8]) -> Option<Vec<u8>> {
+	let mut end = prefix.to_vec();
+	while let Some(0xff) = end.last() {
+		end.pop();
+	}
+	let last = end.last_mut()?;
+	*last += 1;
+	Some(end)
+}
+
+/// Size statistics of a single column, see `KeyValueDB::column_stats`.
+///
+/// Depending on the backend the values may be estimates.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ColumnStats {
+	/// Number of keys in the column.
+	pub num_keys: u64,
+	/// Size of the keys and values in the column, in bytes.
+	pub live_data_size: u64,
+	/// Number of SST files backing the column.
+	pub num_sst_files: u64,
+	/// Size of the column data held in memory, in bytes.
+	pub memtable_size: u64,
+}
+
+/// A read-only view of a key-value database, pinned at the point in time it was taken.
+///
+/// Writes made to the database after the snapshot was created are not observable through it.
+pub trait KeyValueDBSnapshot {
+	/// Get a value by key.
+	fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>>;
+
+	/// Get a value by partial key. Only works for flushed data.
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Result<Option<Box<[u8]>>>;
+
+	/// Iterate over flushed data for a given column.
+	fn iter<'a>(&'a self, col: Option<u32>)
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>>;
+
+	/// Iterate over flushed data for a given column, starting from a given prefix.
+	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>>;
+}
+
 /// Generic key-value database.
 ///
 /// This makes a distinction between "buffered" and "flushed" values. Values which have been
@@ -136,38 +320,80 @@ pub trait KeyValueDB: Sync + Send {
 	fn transaction(&self) -> DBTransaction { DBTransaction::new() }
 
 	/// Get a value by key.
-	fn get(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<DBValue>>;
+	fn get(&self, col: Option<u32>, key: &[u8]) -> Result<Option<DBValue>>;
+
+	/// Get values for multiple keys at once, in the order of `keys`.
+	///
+	/// The default implementation calls `get` for every key.
+	fn get_many(&self, col: Option<u32>, keys: &[&[u8]]) -> Result<Vec<Option<DBValue>>> {
+		keys.iter().map(|key| self.get(col, key)).collect()
+	}
 
 	/// Get a value by partial key. Only works for flushed data.
-	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>>;
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Result<Option<Box<[u8]>>>;
 
 	/// Write a transaction of changes to the buffer.
 	fn write_buffered(&self, transaction: DBTransaction);
 
 	/// Write a transaction of changes to the backing store.
-	fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+	fn write(&self, transaction: DBTransaction) -> Result<()> {
 		self.write_buffered(transaction);
 		self.flush()
 	}
 
 	/// Flush all buffered data.
-	fn flush(&self) -> io::Result<()>;
+	fn flush(&self) -> Result<()>;
 
 	/// Iterate over flushed data for a given column.
 	fn iter<'a>(&'a self, col: Option<u32>)
-		-> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>;
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>>;
 
 	/// Iterate over flushed data for a given column, starting from a given prefix.
 	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
-		-> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>;
+		-> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>>;
+
+	/// Iterate over flushed data for a given column, visiting only the keys between the
+	/// `start` and `end` bounds. With `Direction::Reverse` iteration starts from `end`.
+	///
+	/// The default implementation filters the output of `iter`.
+	fn iter_range<'a>(
+		&'a self,
+		col: Option<u32>,
+		start: Bound<&'a [u8]>,
+		end: Bound<&'a [u8]>,
+		direction: Direction,
+	) -> Result<Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>> {
+		let iter = self.iter(col)?
+			.skip_while(move |(k, _)| !is_after_start(k, start))
+			.take_while(move |(k, _)| is_before_end(k, end));
+		Ok(match direction {
+			Direction::Forward => Box::new(iter),
+			Direction::Reverse => Box::new(iter.collect::<Vec<_>>().into_iter().rev()),
+		})
+	}
+
+	/// Get size statistics of a column. Only accounts for flushed data.
+	///
+	/// The default implementation iterates over the whole column.
+	fn column_stats(&self, col: Option<u32>) -> Result<ColumnStats> {
+		let mut stats = ColumnStats::default();
+		for (key, value) in self.iter(col)? {
+			stats.num_keys += 1;
+			stats.live_data_size += (key.len() + value.len()) as u64;
+		}
+		Ok(stats)
+	}
+
+	/// Take a consistent read-only view of the database.
+	fn snapshot<'a>(&'a self) -> Result<Box<dyn KeyValueDBSnapshot + 'a>>;
 
 	/// Attempt to replace this database with a new one located at the given path.
-	fn restore(&self, new_db: &str) -> io::Result<()>;
+	fn restore(&self, new_db: &str) -> Result<()>;
 }
 
 /// Generic key-value database handler. This trait contains one function `open`.
 /// When called, it opens database with a predefined config.
 pub trait KeyValueDBHandler: Send + Sync {
 	/// Open the predefined key-value database.
-	fn open(&self, path: &Path) -> io::Result<Arc<dyn KeyValueDB>>;
+	fn open(&self, path: &Path) -> Result<Arc<dyn KeyValueDB>>;
 }
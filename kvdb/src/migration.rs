@@ -0,0 +1,263 @@
+// Copyright 2015-2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Schema versioning and migrations of key-value databases.
+//!
+//! The schema version of a database is stored under `VERSION_KEY` in the default column.
+//! A `Manager` runs the registered migrations newer than that version in order. Migrations
+//! are performed in steps, and the progress of the current migration is written together
+//! with the changes of each step, so an interrupted migration resumes where it stopped.
+
+use std::ops::Bound;
+
+use crate::{DBTransaction, Direction, Error, KeyValueDB, Result};
+
+/// Key in the default column holding the schema version of the database.
+pub const VERSION_KEY: &[u8] = b"\0kvdb-schema-version";
+/// Key in the default column holding the progress of an unfinished migration.
+pub const PROGRESS_KEY: &[u8] = b"\0kvdb-migration-progress";
+
+// Prefix of the reserved keys, which are never passed to migrations.
+const RESERVED_PREFIX: &[u8] = b"\0kvdb-";
+
+/// A database whose columns can be added and dropped.
+pub trait ColumnManager {
+	/// The number of non-default columns.
+	fn num_columns(&self) -> u32;
+
+	/// Add a column at the end.
+	fn add_column(&self) -> Result<()>;
+
+	/// Drop the last column.
+	fn drop_column(&self) -> Result<()>;
+}
+
+/// A database which can be migrated.
+pub trait MigrationDB: KeyValueDB + ColumnManager {}
+
+impl<T: KeyValueDB + ColumnManager> MigrationDB for T {}
+
+/// A migration of a database to a schema version.
+pub trait Migration {
+	/// The schema version of the database after the migration.
+	fn version(&self) -> u32;
+
+	/// Perform the next step of the migration, adding the changes to `batch`.
+	///
+	/// `progress` is the value returned by the previous step, or `None` for the first one.
+	/// Returns the progress to resume from, or `None` if the migration is complete.
+	/// The changes and the progress are written atomically, but steps may also change
+	/// the database directly as long as repeating them is harmless.
+	fn step(&self, db: &dyn MigrationDB, progress: Option<&[u8]>, batch: &mut DBTransaction) -> Result<Option<Vec<u8>>>;
+}
+
+/// Adds a column with the given index, unless the database already has it.
+pub struct AddColumn {
+	/// Schema version after the migration.
+	pub version: u32,
+	/// Index of the added column, equal to the number of columns before the migration.
+	pub column: u32,
+}
+
+impl Migration for AddColumn {
+	fn version(&self) -> u32 {
+		self.version
+	}
+
+	fn step(&self, db: &dyn MigrationDB, _progress: Option<&[u8]>, _batch: &mut DBTransaction) -> Result<Option<Vec<u8>>> {
+		while db.num_columns() <= self.column {
+			db.add_column()?;
+		}
+		Ok(None)
+	}
+}
+
+/// Drops the column with the given index, which must be the last one, unless it is already gone.
+pub struct DropColumn {
+	/// Schema version after the migration.
+	pub version: u32,
+	/// Index of the dropped column.
+	pub column: u32,
+}
+
+impl Migration for DropColumn {
+	fn version(&self) -> u32 {
+		self.version
+	}
+
+	fn step(&self, db: &dyn MigrationDB, _progress: Option<&[u8]>, _batch: &mut DBTransaction) -> Result<Option<Vec<u8>>> {
+		match db.num_columns() {
+			n if n == self.column + 1 => db.drop_column()?,
+			n if n <= self.column => {},
+			_ => return Err(Error::Migration(format!("Column {} is not the last column", self.column))),
+		}
+		Ok(None)
+	}
+}
+
+/// Rewrites the keys and values of a column into another column, or the same one,
+/// in batches of `batch_size` keys.
+///
+/// `rewrite` returns the new key and value, or `None` to drop the entry. The original key is
+/// deleted when rewriting within a column. Rewritten keys must then not be greater than the
+/// original ones, otherwise they are rewritten again.
+pub struct RewriteColumn<F> {
+	/// Schema version after the migration.
+	pub version: u32,
+	/// Column to read from.
+	pub from: Option<u32>,
+	/// Column to write to. It must not be the default column, which holds the schema version.
+	pub to: Option<u32>,
+	/// Number of keys rewritten in each step, which must not be zero.
+	pub batch_size: usize,
+	/// The rewrite function.
+	pub rewrite: F,
+}
+
+impl<F> Migration for RewriteColumn<F>
+	where F: Fn(&[u8], &[u8]) -> Option<(Vec<u8>, Vec<u8>)>
+{
+	fn version(&self) -> u32 {
+		self.version
+	}
+
+	fn step(&self, db: &dyn MigrationDB, progress: Option<&[u8]>, batch: &mut DBTransaction) -> Result<Option<Vec<u8>>> {
+		// no key would ever be rewritten.
+		if self.batch_size == 0 {
+			return Err(Error::Migration("The batch size of a column rewrite must not be zero".into()));
+		}
+		// rewritten keys could replace the schema version or the progress of the migration.
+		if self.to.is_none() {
+			return Err(Error::Migration("A column rewrite must not write to the default column".into()));
+		}
+		let start = progress.map_or(Bound::Unbounded, Bound::Excluded);
+		let mut last_key = None;
+		let entries = db.iter_range(self.from, start, Bound::Unbounded, Direction::Forward)?
+			.filter(|(key, _)| !(self.from.is_none() && key.starts_with(RESERVED_PREFIX)))
+			.take(self.batch_size);
+		for (key, value) in entries {
+			if self.from == self.to {
+				batch.delete(self.from, &key);
+			}
+			if let Some((new_key, new_value)) = (self.rewrite)(&key, &value) {
+				batch.put_vec(self.to, &new_key, new_value);
+			}
+			last_key = Some(key.into_vec());
+		}
+		Ok(last_key)
+	}
+}
+
+/// Runs migrations of a database.
+#[derive(Default)]
+pub struct Manager {
+	migrations: Vec<Box<dyn Migration>>,
+}
+
+impl Manager {
+	/// Create a manager without migrations.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Register the next migration. Its version must be greater than the version of the previous one.
+	pub fn add_migration<M: Migration + 'static>(&mut self, migration: M) -> Result<()> {
+		if let Some(last) = self.migrations.last() {
+			if migration.version() <= last.version() {
+				return Err(Error::Migration(format!(
+					"Migration to version {} is registered after version {}", migration.version(), last.version()
+				)));
+			}
+		}
+		self.migrations.push(Box::new(migration));
+		Ok(())
+	}
+
+	/// Whether the database needs to be migrated.
+	pub fn is_needed(&self, db: &dyn MigrationDB) -> Result<bool> {
+		let version = version(db).map_err(|err| migration_error("Failed to read the schema version", err))?;
+		Ok(self.migrations.last().map_or(false, |m| m.version() > version))
+	}
+
+	/// Run all the migrations newer than the schema version of the database.
+	/// Resumes a migration which was interrupted.
+	///
+	/// Returns the schema version of the database after the migrations.
+	/// Errors are reported as `Error::Migration`, with the version and step that failed.
+	pub fn migrate(&self, db: &dyn MigrationDB) -> Result<u32> {
+		let mut version = version(db).map_err(|err| migration_error("Failed to read the schema version", err))?;
+		for migration in &self.migrations {
+			if migration.version() <= version {
+				continue;
+			}
+			let context = |step: u32| format!("Migration to version {} failed in step {}", migration.version(), step);
+			let mut progress = match db.get(None, PROGRESS_KEY).map_err(|err| migration_error(&context(0), err))? {
+				Some(ref value) if value.len() >= 4 && decode_version(&value[..4])? == migration.version() =>
+					Some(value[4..].to_vec()),
+				_ => None,
+			};
+
+			// steps are counted from the one the migration is resumed from.
+			for step in 0.. {
+				let mut batch = DBTransaction::new();
+				progress = migration.step(db, progress.as_ref().map(|p| &p[..]), &mut batch)
+					.map_err(|err| migration_error(&context(step), err))?;
+				match progress {
+					Some(ref progress) => {
+						let mut value = migration.version().to_be_bytes().to_vec();
+						value.extend_from_slice(progress);
+						batch.put(None, PROGRESS_KEY, &value);
+					},
+					None => {
+						batch.delete(None, PROGRESS_KEY);
+						batch.put(None, VERSION_KEY, &migration.version().to_be_bytes());
+					},
+				}
+				db.write(batch).map_err(|err| migration_error(&context(step), err))?;
+				if progress.is_none() {
+					break;
+				}
+			}
+			version = migration.version();
+		}
+		Ok(version)
+	}
+}
+
+/// Get the schema version of a database, `0` if it was never migrated.
+pub fn version<D: KeyValueDB + ?Sized>(db: &D) -> Result<u32> {
+	match db.get(None, VERSION_KEY)? {
+		Some(value) => decode_version(&value),
+		None => Ok(0),
+	}
+}
+
+// Wrap an error in a migration error describing what failed.
+fn migration_error(context: &str, err: Error) -> Error {
+	match err {
+		Error::Migration(err) => Error::Migration(format!("{}: {}", context, err)),
+		err => Error::Migration(format!("{}: {}", context, err)),
+	}
+}
+
+fn decode_version(bytes: &[u8]) -> Result<u32> {
+	if bytes.len() != 4 {
+		return Err(Error::Corruption("Invalid schema version".into()));
+	}
+	let mut version = [0u8; 4];
+	version.copy_from_slice(bytes);
+	Ok(u32::from_be_bytes(version))
+}
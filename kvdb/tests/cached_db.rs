@@ -0,0 +1,86 @@
+// Copyright 2015-2019 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests of `CachedDB` over an in-memory database.
+
+use kvdb::{CachedDB, KeyValueDB};
+use kvdb_memorydb::create;
+
+#[test]
+fn cached_db() {
+	let db = CachedDB::new(create(1), 1024 * 1024);
+	let mut batch = db.transaction();
+	batch.put(Some(0), b"foo", b"bar");
+	batch.put(Some(0), b"hello", b"world");
+	db.write(batch).unwrap();
+
+	assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), b"bar");
+	assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), b"bar");
+	assert!(db.get(Some(0), b"missing").unwrap().is_none());
+	assert!(db.get(Some(0), b"missing").unwrap().is_none());
+	let values = db.get_many(Some(0), &[b"foo", b"hello"]).unwrap();
+	assert_eq!(values[1].as_ref().map(|v| &v[..]), Some(&b"world"[..]));
+	let stats = db.cache_stats();
+	assert_eq!((stats.hits, stats.misses), (3, 3));
+	assert!(stats.size > 0);
+
+	// writes invalidate the cached values.
+	let mut batch = db.transaction();
+	batch.put(Some(0), b"foo", b"changed");
+	batch.put(Some(0), b"missing", b"found");
+	db.write_buffered(batch);
+	assert_eq!(&*db.get(Some(0), b"foo").unwrap().unwrap(), b"changed");
+	assert_eq!(&*db.get(Some(0), b"missing").unwrap().unwrap(), b"found");
+	let mut batch = db.transaction();
+	batch.delete(Some(0), b"foo");
+	batch.delete_prefix(Some(0), b"mis");
+	db.write_buffered(batch);
+	assert!(db.get(Some(0), b"foo").unwrap().is_none());
+	assert!(db.get(Some(0), b"missing").unwrap().is_none());
+	assert_eq!(db.cache_stats().misses, 7);
+
+	assert!(db.get(Some(1), b"foo").is_err());
+	let db = db.into_inner();
+	assert!(db.get(Some(0), b"foo").unwrap().is_none());
+}
+
+#[test]
+fn cached_db_is_bounded() {
+	let capacity = 4096;
+	let db = CachedDB::new(create(1), capacity).with_column_capacity(None, 0);
+	let mut batch = db.transaction();
+	for i in 0u8..100 {
+		batch.put(Some(0), &[i], &[i; 100]);
+	}
+	batch.put(None, b"foo", b"bar");
+	db.write(batch).unwrap();
+
+	for i in 0u8..100 {
+		assert_eq!(&*db.get(Some(0), &[i]).unwrap().unwrap(), &[i; 100][..]);
+	}
+	let size = db.cache_stats().size;
+	assert!(size > 0 && size <= capacity as u64);
+	// the most recently read values are kept.
+	assert!(db.get(Some(0), &[99]).is_ok());
+	assert_eq!(db.cache_stats().hits, 1);
+	assert!(db.get(Some(0), &[0]).is_ok());
+	assert_eq!(db.cache_stats().hits, 1);
+
+	// the default column is not cached.
+	assert_eq!(&*db.get(None, b"foo").unwrap().unwrap(), b"bar");
+	assert_eq!(&*db.get(None, b"foo").unwrap().unwrap(), b"bar");
+	assert_eq!(db.cache_stats().hits, 1);
+}